@@ -0,0 +1,67 @@
+-- Schema for local development with the sqlite feature, e.g.:
+-- sqlite3 dev.db < schema/sqlite.sql
+-- DATABASE_CONNECTION_URL=sqlite://dev.db
+-- Ids are hyphenated lowercase uuids, timestamps are 'YYYY-MM-DD HH:MM:SS' (UTC).
+
+CREATE TABLE IF NOT EXISTS video (
+    uuid TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    comments INTEGER NOT NULL DEFAULT 0,
+    up_votes INTEGER NOT NULL DEFAULT 0,
+    down_votes INTEGER NOT NULL DEFAULT 0,
+    views INTEGER NOT NULL DEFAULT 0,
+    viewtime_seconds INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    hashtags TEXT, -- JSON array
+    status INTEGER NOT NULL
+);
+
+-- The candidate pool loads the newest ready videos
+CREATE INDEX IF NOT EXISTS video_status_created_at ON video (status, created_at);
+
+CREATE TABLE IF NOT EXISTS liked_videos (
+    user_id TEXT NOT NULL,
+    video_id TEXT NOT NULL,
+    liked_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS viewed_video (
+    user_id TEXT NOT NULL,
+    video_id TEXT NOT NULL,
+    viewed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS disliked_videos (
+    user_id TEXT NOT NULL,
+    video_id TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_followed_users (
+    user_id TEXT NOT NULL,
+    followed_users TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_liked_hastags (
+    user_id TEXT NOT NULL,
+    hashtags TEXT NOT NULL, -- JSON array
+    timestamp TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS user_not_interested_hashtags (
+    user_id TEXT NOT NULL,
+    hashtag TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_not_interested_users (
+    user_id TEXT NOT NULL,
+    not_interested_users TEXT NOT NULL
+);
+
+-- Optional normalized hashtags, used instead of video.hashtags if the
+-- videoHashtags section of the schema config is set, e.g. filled with:
+-- INSERT INTO video_hashtags SELECT uuid, value FROM video, json_each(video.hashtags);
+CREATE TABLE IF NOT EXISTS video_hashtags (
+    video_id TEXT NOT NULL,
+    hashtag TEXT NOT NULL,
+    PRIMARY KEY (video_id, hashtag)
+);
@@ -1,18 +1,26 @@
 use std::{
     collections::{HashMap, HashSet},
-    error::Error,
     time::Instant,
 };
 
 use crate::{
     config::Config,
-    database::{self, User, Video},
+    database::{User, UserId, Video, VideoId},
+    hashtag_graph::HashtagGraph,
+    indexes::Indexes,
+    score_index::ScoreIndex,
+    scoring::{self, DefaultScorer, ScoreExplanation, Scorer},
+    store::{StoreError, VideoStore},
 };
 use log::debug;
-use rand::{distr::{weighted::WeightedIndex, Distribution}, random_bool};
-use sqlx::MySqlPool;
+use rand::{distr::{weighted::WeightedIndex, Distribution}, Rng};
 
-fn sort_out_repeated_videos(config: &Config, videos: &mut Vec<Video>, user: &User) {
+fn sort_out_repeated_videos(
+    config: &Config,
+    videos: &mut Vec<Video>,
+    user: &User,
+    rng: &mut impl Rng,
+) {
     let videos_set: HashSet<_> = videos.iter().map(|video| video.uuid.clone()).collect();
     let common: HashSet<_> = user
         .last_viewed
@@ -21,12 +29,12 @@ fn sort_out_repeated_videos(config: &Config, videos: &mut Vec<Video>, user: &Use
         .cloned()
         .collect();
     videos.retain(|video| {
-        !random_bool(config.selecting.already_watched_video_sort_out_probability)
+        !rng.random_bool(config.selecting.already_watched_video_sort_out_probability)
             && !common.contains(&video.uuid)
     });
 }
 
-fn weighted_random<T>(vec: &Vec<T>, decay_factor: f64) -> Option<T>
+fn weighted_random<T>(vec: &[T], decay_factor: f64, rng: &mut impl Rng) -> Option<T>
 where
     T: Clone,
 {
@@ -35,23 +43,26 @@ where
         return None;
     }
 
-    let weights: Vec<f64> = (0..len)
-        .map(|i| {
-            let weight = decay_factor.powi(i as i32);
-            weight
-        })
-        .collect();
+    let weights: Vec<f64> = (0..len).map(|i| decay_factor.powi(i as i32)).collect();
 
     let dist = WeightedIndex::new(&weights).unwrap();
-    let index = dist.sample(&mut rand::rng());
+    let index = dist.sample(rng);
     Some(vec[index].clone())
 }
 
-fn score_sort_videos(videos: Vec<Video>, user: &User, config: &Config) -> Vec<Video> {
+fn score_sort_videos(
+    videos: Vec<Video>,
+    user: &User,
+    config: &Config,
+    scores: &ScoreIndex,
+) -> Vec<Video> {
     let mut scored_random_vids = videos
         .into_iter()
         .map(|mut video| {
-            video.score = score_video_personalized(user, &video, config);
+            video.score = match scores.get(&video.uuid, config) {
+                Some(score) => personalize_score(score, user, &video, config),
+                None => score_video_personalized(user, &video, config),
+            };
             video
         })
         .collect::<Vec<Video>>();
@@ -61,10 +72,16 @@ fn score_sort_videos(videos: Vec<Video>, user: &User, config: &Config) -> Vec<Vi
     scored_random_vids
 }
 
-fn sort_user_hashtags_by_frequency(user: &User) -> Vec<String> {
+/// The hashtags of the user with how often they occur, the most frequent first.
+fn sort_user_hashtags_by_frequency(user: &User) -> Vec<(String, usize)> {
     let mut frequency_map: HashMap<&String, usize> = HashMap::new();
 
-    for hashtag in &user.last_hashtags {
+    // Hashtags the user is not interested in are never selected
+    for hashtag in user
+        .last_hashtags
+        .iter()
+        .filter(|hashtag| !user.not_interested_hashtags.contains(hashtag))
+    {
         *frequency_map.entry(hashtag).or_insert(0) += 1;
     }
 
@@ -73,77 +90,406 @@ fn sort_user_hashtags_by_frequency(user: &User) -> Vec<String> {
 
     sorted_hashtags
         .into_iter()
-        .map(|(hashtag, _)| hashtag.clone())
+        .map(|(hashtag, frequency)| (hashtag.clone(), frequency))
+        .collect()
+}
+
+/// Picks `amount` different hashtags with `weighted_random`, so the frequent
+/// ones are likely but not guaranteed to be chosen.
+fn sample_hashtags(
+    sorted_hashtags: Vec<(String, usize)>,
+    amount: usize,
+    decay_factor: f64,
+    rng: &mut impl Rng,
+) -> Vec<(String, usize)> {
+    let mut remaining = sorted_hashtags;
+    let mut sampled = Vec::new();
+    while sampled.len() < amount {
+        let Some(hashtag) = weighted_random(&remaining, decay_factor, rng) else {
+            break;
+        };
+
+        remaining.retain(|other| other.0 != hashtag.0);
+        sampled.push(hashtag);
+    }
+
+    sampled
+}
+
+/// Adds the hashtags most related to the sampled ones (used on the same videos), so users
+/// with a niche hashtag get more than its few videos. A related hashtag gets the
+/// frequency of the hashtag it is related to, weakened by the similarity and the expansion weight.
+fn expand_hashtags(
+    hashtags: Vec<(String, usize)>,
+    graph: &HashtagGraph,
+    user: &User,
+    expansion_weight: f64,
+) -> Vec<(String, f64)> {
+    let mut expanded: Vec<(String, f64)> = hashtags
+        .iter()
+        .map(|(hashtag, frequency)| (hashtag.clone(), *frequency as f64))
+        .collect();
+
+    if expansion_weight <= 0. {
+        return expanded;
+    }
+
+    for (hashtag, frequency) in &hashtags {
+        for (related, similarity) in graph.related_hashtags(hashtag) {
+            if user.not_interested_hashtags.contains(related) {
+                continue;
+            }
+
+            let weight = *frequency as f64 * similarity * expansion_weight;
+            match expanded.iter_mut().find(|(other, _)| other == related) {
+                Some((_, other_weight)) => *other_weight = other_weight.max(weight),
+                None => expanded.push((related.clone(), weight)),
+            }
+        }
+    }
+
+    expanded
+}
+
+/// Splits the hashtag video fetch amount between the hashtags proportional
+/// to their weight, the quotas add up to exactly the fetch amount. Every hashtag
+/// gets at least one video, if there are more hashtags than videos to fetch only the
+/// heaviest hashtags get one. The rest is split by the largest remainder.
+fn hashtag_quotas(hashtags: &[(String, f64)], fetch_amount: u32) -> Vec<(String, u32)> {
+    let mut by_weight: Vec<&(String, f64)> = hashtags.iter().collect();
+    by_weight.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    by_weight.truncate(fetch_amount as usize);
+
+    let rest = fetch_amount - by_weight.len() as u32;
+    let total_weight: f64 = by_weight.iter().map(|(_, weight)| weight).sum();
+    let shares: Vec<f64> = by_weight
+        .iter()
+        .map(|(_, weight)| {
+            if total_weight > 0. {
+                weight / total_weight * rest as f64
+            } else {
+                rest as f64 / by_weight.len() as f64
+            }
+        })
+        .collect();
+
+    let mut quotas: Vec<u32> = shares.iter().map(|share| 1 + share.floor() as u32).collect();
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    by_remainder.sort_by(|&a, &b| shares[b].fract().partial_cmp(&shares[a].fract()).unwrap());
+    let missing = fetch_amount.saturating_sub(quotas.iter().sum());
+    for &i in by_remainder.iter().take(missing as usize) {
+        quotas[i] += 1;
+    }
+
+    by_weight
+        .into_iter()
+        .zip(quotas)
+        .map(|((hashtag, _), quota)| (hashtag.clone(), quota))
         .collect()
 }
 
 fn select_high_or_low_score_video(
     final_sort: &mut Vec<Video>,
-    source: &Vec<Video>,
+    source: &[Video],
     counter: &mut usize,
     probability: f64,
     i: usize,
+    rng: &mut impl Rng,
 ) {
-    if random_bool(probability) {
+    if rng.random_bool(probability) {
         // Matching hashtag & high score
-        let video = source.get(source.len() - *counter - 1).unwrap();
-        final_sort.push(video.clone());
-        *counter += 1;
+        let index = source.len().checked_sub(*counter + 1);
+        if let Some(video) = index.and_then(|index| source.get(index)) {
+            final_sort.push(video.clone());
+            *counter += 1;
+        }
 
         debug!("    highscore");
         debug!("");
     } else {
         // Matching hashtag & low score
-        final_sort.push(source.get(i).unwrap().clone());
+        if let Some(video) = source.get(i) {
+            final_sort.push(video.clone());
+        }
         debug!("    lowscore");
         debug!("");
     }
 }
 
+/// Sorts the videos by the upper confidence bound (UCB1) of their like to view ratio.
+/// The fewer views a video has, the more uncertain its ratio is and the higher the bonus
+/// on top of it. So unknown videos get a chance, but ones that got few likes
+/// with their views fall back. Once a video has `exploration_max_views` it is not
+/// fetched as fresh video anymore and has to compete by its score.
+fn rank_by_upper_confidence_bound(videos: Vec<Video>) -> Vec<Video> {
+    let total_views: f64 = videos.iter().map(|video| video.views.max(0) as f64).sum();
+    let upper_confidence_bound = |video: &Video| {
+        let views = video.views.max(0) as f64;
+        let like_ratio = (video.upvotes.max(0) as f64 + 1.) / (views + 2.);
+        like_ratio + (2. * (total_views + 1.).ln() / (views + 1.)).sqrt()
+    };
+
+    let mut ranked: Vec<(f64, Video)> = videos
+        .into_iter()
+        .map(|video| (upper_confidence_bound(&video), video))
+        .collect();
+    ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    ranked.into_iter().map(|(_, video)| video).collect()
+}
+
+/// The videos with the given ids, in the order of the ids.
+fn videos_in_order(videos: &[Video], ids: &[VideoId]) -> Vec<Video> {
+    ids.iter()
+        .filter_map(|id| videos.iter().find(|video| video.uuid == *id))
+        .cloned()
+        .collect()
+}
+
+/// How personalized the feed of the user can be, from 0.0 (brand new user) to
+/// 1.0 (enough likes, follows and views). The rest of a batch is filled with cold start videos.
+fn personalization_level(user: &User, config: &Config) -> f64 {
+    let needed = config.cold_start.full_personalization_interactions;
+    if needed == 0 {
+        return 1.;
+    }
+
+    let interactions = user.liked_videos.len() + user.following.len() + user.last_viewed.len();
+    (interactions as f64 / needed as f64).min(1.)
+}
+
+/// Keeps one video per hashtag, so the videos cover as many different hashtags as possible.
+fn select_diverse_hashtag_videos(videos: Vec<Video>) -> Vec<Video> {
+    let mut covered: HashSet<String> = HashSet::new();
+    videos
+        .into_iter()
+        .filter(|video| {
+            let is_new = video.hashtags.iter().any(|hashtag| !covered.contains(hashtag));
+            covered.extend(video.hashtags.iter().cloned());
+            is_new
+        })
+        .collect()
+}
+
+/// Cold start videos for users without (enough) interactions: globally popular,
+/// recently trending and videos of as many hashtags as possible, taken in turns.
+async fn cold_start_videos(
+    user: &User,
+    config: &Config,
+    store: &dyn VideoStore,
+    indexes: &Indexes,
+    served: &HashSet<VideoId>,
+    amount: usize,
+    rng: &mut impl Rng,
+) -> Result<Vec<Video>, StoreError> {
+    let random = indexes
+        .candidates
+        .read()
+        .unwrap()
+        .random_videos(config.cold_start.diverse_hashtag_fetch_amount, rng);
+    let (popular, trending) = store.fetch_cold_start_videos(config).await?;
+    let sources = [popular, trending, select_diverse_hashtag_videos(random)];
+
+    let mut selected: Vec<Video> = Vec::new();
+    let longest_source = sources.iter().map(Vec::len).max().unwrap_or(0);
+    for i in 0..longest_source {
+        for video in sources.iter().filter_map(|source| source.get(i)) {
+            if selected.len() >= amount {
+                return Ok(selected);
+            }
+
+            let is_repeated = served.contains(&video.uuid)
+                || user.rejects(video)
+                || user.last_viewed.contains(&video.uuid)
+                || selected.iter().any(|other| other.uuid == video.uuid);
+            if !is_repeated {
+                selected.push(video.clone());
+            }
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Re-ranks the selected videos so one creator can not fill the batch:
+/// every creator may have `max_videos_per_creator` videos and there need to be
+/// `min_same_creator_gap` other videos between two of the same creator. A selected
+/// video that would break the rules is moved back, if no selected video fits the
+/// slot, the best fitting backfill video is used. If none fits at all, the batch ends.
+/// This also removes videos that were selected twice.
+fn diversify_creators(selected: Vec<Video>, backfill: Vec<Video>, config: &Config) -> Vec<Video> {
+    let max_per_creator = config.selecting.max_videos_per_creator as usize;
+    let min_gap = config.selecting.min_same_creator_gap as usize;
+    let target_len = selected.len();
+
+    let mut pending = selected;
+    let mut backfill = backfill;
+    let mut result: Vec<Video> = Vec::with_capacity(target_len);
+    let mut per_creator: HashMap<UserId, usize> = HashMap::new();
+
+    while result.len() < target_len {
+        let fits = |video: &Video| {
+            let recent = &result[result.len().saturating_sub(min_gap)..];
+            per_creator.get(&video.user_id).copied().unwrap_or(0) < max_per_creator
+                && !recent.iter().any(|other| other.user_id == video.user_id)
+                && !result.iter().any(|other| other.uuid == video.uuid)
+        };
+
+        let video = if let Some(pos) = pending.iter().position(fits) {
+            pending.remove(pos)
+        } else if let Some(pos) = backfill.iter().position(fits) {
+            debug!("    backfilled creator slot");
+            backfill.remove(pos)
+        } else {
+            break;
+        };
+
+        *per_creator.entry(video.user_id.clone()).or_insert(0) += 1;
+        result.push(video);
+    }
+
+    result
+}
+
+/// Selects the next videos for the user, without the already `served` videos of
+/// the feed session. Every random decision is made with the given rng, so a feed
+/// can be replayed by seeding it the same way (as long as the candidate pool and
+/// the database return the same candidates).
 pub async fn next_videos(
     user: &User,
     config: &Config,
-    db_pool: &MySqlPool,
-) -> Result<Vec<Video>, Box<dyn Error>> {
+    store: &dyn VideoStore,
+    indexes: &Indexes,
+    served: &HashSet<VideoId>,
+    rng: &mut impl Rng,
+) -> Result<Vec<Video>, StoreError> {
     let start_time = Instant::now();
     debug!(
         "User: likes: {:?}, followed: {:?}, hashtags: {:?}",
         user.liked_videos, user.following, user.last_hashtags
     );
-    let selected_hashtag = weighted_random(
-        &sort_user_hashtags_by_frequency(user), // Sorts by frequency, so i = 0 is the most "liked" hashtag
+    let selected_hashtags = sample_hashtags(
+        sort_user_hashtags_by_frequency(user), // Sorts by frequency, so i = 0 is the most "liked" hashtag
+        config.selecting.blended_hashtags_amount as usize,
         config.selecting.select_high_freq_hashtag_probability,
+        rng,
+    );
+    let selected_hashtags = expand_hashtags(
+        selected_hashtags,
+        &indexes.hashtag_graph.read().unwrap(),
+        user,
+        config.selecting.hashtag_expansion_weight,
+    );
+    let hashtag_quotas = hashtag_quotas(
+        &selected_hashtags,
+        config.selecting.next_videos_fetch_amount_matching_hashtag,
+    );
+
+    let related_ids = indexes.co_likes.read().unwrap().related_videos(
+        &user.liked_videos,
+        config.collaborative.related_fetch_amount as usize,
+    );
+
+    let trending_ids: Vec<VideoId> = indexes
+        .trending
+        .read()
+        .unwrap()
+        .videos()
+        .iter()
+        .filter(|video| !user.last_viewed.contains(&video.uuid))
+        .take(config.trending.candidate_amount as usize)
+        .map(|video| video.uuid.clone())
+        .collect();
+
+    // Random and hashtag videos are sampled from the candidate pool
+    let (mut random_videos, mut hashtag_videos) = {
+        let candidates = indexes.candidates.read().unwrap();
+        (
+            candidates.random_videos(config.selecting.next_videos_fetch_amount_random, rng),
+            candidates.hashtag_videos(&hashtag_quotas, rng),
+        )
+    };
+
+    // Related and trending videos are fetched by their ids in one query
+    let ids = [related_ids.as_slice(), &trending_ids].concat();
+    let fetch_start_time = Instant::now();
+    let (mut fresh_videos, mut id_videos) = tokio::try_join!(
+        store.fetch_fresh_videos(config),
+        store.fetch_videos_by_ids(&ids)
+    )?;
+    debug!(
+        "Fetching videos took: {} ms",
+        fetch_start_time.elapsed().as_millis()
     );
 
-    let mut fetched_videos = database::fetch_next_videos(
-        config,
-        selected_hashtag.clone().unwrap_or_else(|| "/".into()),
-        db_pool,
-    )
-    .await?;
+    random_videos.retain(|video| !served.contains(&video.uuid) && !user.rejects(video));
+    hashtag_videos.retain(|video| !served.contains(&video.uuid) && !user.rejects(video));
+    fresh_videos.retain(|video| !served.contains(&video.uuid) && !user.rejects(video));
+    id_videos.retain(|video| !served.contains(&video.uuid) && !user.rejects(video));
+    sort_out_repeated_videos(config, &mut random_videos, user, rng);
+    sort_out_repeated_videos(config, &mut hashtag_videos, user, rng);
+    sort_out_repeated_videos(config, &mut fresh_videos, user, rng);
 
-    sort_out_repeated_videos(config, &mut fetched_videos.0, user);
-    sort_out_repeated_videos(config, &mut fetched_videos.1, user);
+    // Fresh videos, the most promising to explore first
+    let mut exploration_videos = rank_by_upper_confidence_bound(fresh_videos).into_iter();
 
-    // Hashtag videos orderd by high and low score
-    let sorted_hashtag_videos = score_sort_videos(fetched_videos.1, user, config);
+    // Videos liked by the same users, most related first
+    let mut related_videos = videos_in_order(&id_videos, &related_ids).into_iter();
 
-    // Random videos orderd by high and low score
-    let sorted_rand_scored_vids = score_sort_videos(fetched_videos.0, user, config);
+    // Videos taking off right now, most trending first
+    let mut trending_videos = videos_in_order(&id_videos, &trending_ids).into_iter();
+
+    // Hashtag and random videos orderd by high and low score
+    let (sorted_hashtag_videos, sorted_rand_scored_vids) = {
+        let scores = indexes.scores.read().unwrap();
+        (
+            score_sort_videos(hashtag_videos, user, config, &scores),
+            score_sort_videos(random_videos, user, config, &scores),
+        )
+    };
 
     let mut final_sort: Vec<Video> = Vec::new();
 
     let mut high_score_rand_video_chosen = 0;
 
-    debug!("Selected Hashtag: {:?}", selected_hashtag);
+    // New users get cold start videos, the more they interact the less
+    let personalization_level = personalization_level(user, config);
+    let max_amount = config.selecting.max_next_videos_amount as usize;
+    let cold_start_amount = ((1. - personalization_level) * max_amount as f64).round() as usize;
+    debug!("Personalization level: {personalization_level}, cold start videos: {cold_start_amount}");
+
+    debug!("Selected Hashtags (with quotas): {:?}", hashtag_quotas);
     let mut high_score_hashtag_video_chosen = 0;
     for i in 0..sorted_rand_scored_vids.len() + sorted_hashtag_videos.len() {
-        if i >= config.selecting.max_next_videos_amount.try_into().unwrap() {
+        if i >= max_amount - cold_start_amount {
             break;
         }
 
-        if selected_hashtag.is_some()
-            && random_bool(config.selecting.hashtag_2_random_video_probability)
+        if rng.random_bool(config.selecting.exploration_rate) {
+            if let Some(video) = exploration_videos.next() {
+                debug!("+ exploration");
+                final_sort.push(video);
+                continue;
+            }
+        }
+
+        if rng.random_bool(config.selecting.related_video_probability) {
+            if let Some(video) = related_videos.next() {
+                debug!("+ related");
+                final_sort.push(video);
+                continue;
+            }
+        }
+
+        if rng.random_bool(config.selecting.trending_video_probability) {
+            if let Some(video) = trending_videos.next() {
+                debug!("+ trending");
+                final_sort.push(video);
+                continue;
+            }
+        }
+
+        if !hashtag_quotas.is_empty()
+            && rng.random_bool(config.selecting.hashtag_2_random_video_probability)
         {
             debug!("+ hashtag");
             select_high_or_low_score_video(
@@ -152,6 +498,7 @@ pub async fn next_videos(
                 &mut high_score_hashtag_video_chosen,
                 config.selecting.high_score_after_hashtag_video_probability,
                 i,
+                rng,
             );
         } else {
             debug!("+ random");
@@ -161,10 +508,33 @@ pub async fn next_videos(
                 &mut high_score_rand_video_chosen,
                 config.selecting.high_score_video_probability,
                 i,
+                rng,
             );
         }
     }
 
+    // Everything that was not chosen, can fill slots the creator rules make free
+    let chosen: HashSet<&VideoId> = final_sort.iter().map(|video| &video.uuid).collect();
+    let mut remaining: Vec<Video> = sorted_hashtag_videos
+        .iter()
+        .chain(sorted_rand_scored_vids.iter())
+        .filter(|video| !chosen.contains(&video.uuid))
+        .cloned()
+        .collect();
+    remaining.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+    if cold_start_amount > 0 {
+        let cold_start_videos =
+            cold_start_videos(user, config, store, indexes, served, cold_start_amount, rng)
+                .await?;
+        for video in cold_start_videos {
+            let position = rng.random_range(0..=final_sort.len());
+            final_sort.insert(position, video);
+        }
+    }
+
+    let final_sort = diversify_creators(final_sort, remaining, config);
+
     debug!(
         "Next Video Selecting took: {} ms",
         start_time.elapsed().as_millis()
@@ -172,73 +542,218 @@ pub async fn next_videos(
     Ok(final_sort)
 }
 
+/// The scorer selected by the `scorer` name in the scoring config. Falls back to
+/// the default scorer, the name is validated on config load anyway.
+fn scorer(config: &Config) -> &'static dyn Scorer {
+    scoring::by_name(&config.scoring.scorer).unwrap_or(&DefaultScorer)
+}
+
 pub fn score_video(video: &Video, config: &Config) -> f64 {
-    let mut score = 1.;
+    explain_score(video, config).score
+}
+
+pub fn score_video_personalized(user: &User, video: &Video, config: &Config) -> f64 {
+    explain_score_personalized(user, video, config).score
+}
+
+/// Personalizes an already computed (e.g. precomputed) global score.
+pub fn personalize_score(score: f64, user: &User, video: &Video, config: &Config) -> f64 {
+    let mut explanation = ScoreExplanation {
+        score,
+        ..Default::default()
+    };
+    scorer(config).personalize(&mut explanation, user, video, config);
+    explanation.score
+}
+
+pub fn explain_score(video: &Video, config: &Config) -> ScoreExplanation {
+    let mut explanation = scorer(config).explain(video, config);
+    explanation.apply_freshness(scoring::freshness_factor(video, config));
+    explanation
+}
+
+pub fn explain_score_personalized(user: &User, video: &Video, config: &Config) -> ScoreExplanation {
+    let mut explanation = scorer(config).explain_personalized(user, video, config);
+    explanation.apply_freshness(scoring::freshness_factor(video, config));
+    explanation
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{candidate_pool::CandidatePool, memory_store::MemoryStore, store::UserStore};
+
+    const FIXTURE: &str = "fixtures/example.json";
 
-    //Score up with likes
-    score += (video.upvotes as f64 / 10.).powf(config.scoring.upvote_exponent);
+    /// The first user of the fixture, with likes, hashtags and a downvote.
+    const FIXTURE_USER: &str = "962c4706-63bf-2ffe-a59c-217962c3995a";
 
-    //Score up with views
-    score += (video.views as f64 / 10.).powf(config.scoring.view_exponent);
+    fn config() -> Config {
+        serde_json::from_str(include_str!("../config.json")).unwrap()
+    }
 
-    //Score multiplied by like-2-view ratio
-    if video.views != 0 {
-        score *= (video.upvotes as f64 / video.views as f64) * config.scoring.like_2_view_strength;
+    /// The fixture store and the indexes with all fixture videos in the candidate pool.
+    async fn fixture() -> (MemoryStore, Indexes) {
+        let store = MemoryStore::from_fixture(FIXTURE).unwrap();
+        let indexes = Indexes::default();
+        let videos = store.fetch_ready_videos(u32::MAX).await.unwrap();
+        *indexes.candidates.write().unwrap() = CandidatePool::build(videos);
+        (store, indexes)
     }
 
-    //Multiply score by upvote-2-totalvotes ratio
-    //Multiply score by downvote-2-views impact
-    //Multiply score by avg. viewtime per view
-    let total_votes = video.upvotes + video.downvotes;
-    if video.views != 0 {
-        score *= (video.viewtime_seconds as f64 / video.views as f64)
-            * config.scoring.viewtime_per_view_strength;
+    async fn fixture_user(store: &MemoryStore, user_id: &str, config: &Config) -> User {
+        let user_id = UserId::parse(user_id).unwrap();
+        store.fetch_user(&user_id, config).await.unwrap()
+    }
 
-        if total_votes != 0 {
-            let upvote_ratio = video.upvotes as f64 / total_votes as f64;
-            score *= upvote_ratio * config.scoring.upvote_2_totalvotes_strength;
+    async fn next_video_ids(
+        user: &User,
+        config: &Config,
+        store: &MemoryStore,
+        indexes: &Indexes,
+        seed: u64,
+    ) -> Vec<VideoId> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        next_videos(user, config, store, indexes, &HashSet::new(), &mut rng)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|video| video.uuid)
+            .collect()
+    }
 
-            let downvote_impact = video.downvotes as f64 / video.views as f64;
-            score *= (1.0 - downvote_impact).max(0.5);
+    #[tokio::test]
+    async fn next_videos_are_replayed_with_the_same_seed() {
+        let config = config();
+        let (store, indexes) = fixture().await;
+        let user = fixture_user(&store, FIXTURE_USER, &config).await;
+        for seed in 0..20 {
+            let videos = next_video_ids(&user, &config, &store, &indexes, seed).await;
+            assert!(!videos.is_empty());
+            assert_eq!(videos, next_video_ids(&user, &config, &store, &indexes, seed).await);
         }
     }
 
-    if video.comments != 0 {
-        //lower = better
-        let comments_2_votes_ratio = total_votes as f64 / video.comments as f64;
-        score *= (1. / comments_2_votes_ratio) * config.scoring.comments_2_votes_strength;
+    #[tokio::test]
+    async fn next_videos_keep_creators_apart() {
+        let config = config();
+        let (store, indexes) = fixture().await;
+        let user = fixture_user(&store, FIXTURE_USER, &config).await;
+        let max_per_creator = config.selecting.max_videos_per_creator as usize;
+        let min_gap = config.selecting.min_same_creator_gap as usize;
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let videos = next_videos(&user, &config, &store, &indexes, &HashSet::new(), &mut rng)
+                .await
+                .unwrap();
+            for (i, video) in videos.iter().enumerate() {
+                let same_creator = |other: &Video| other.user_id == video.user_id;
+                let creator_videos = videos.iter().filter(|other| same_creator(other)).count();
+                assert!(creator_videos <= max_per_creator);
+                assert!(!videos[i.saturating_sub(min_gap)..i].iter().any(same_creator));
+            }
+        }
     }
 
-    normalize_score(
-        &mut score,
-        &config.scoring.viral_score,
-        config.scoring.normalize_threshold,
-    );
-
-    score
-}
+    #[tokio::test]
+    async fn next_videos_exclude_downvoted_videos_and_not_interesting_creators() {
+        let config = config();
+        let (store, indexes) = fixture().await;
+        let mut user = fixture_user(&store, FIXTURE_USER, &config).await;
+        // The creator with the most videos in the fixture
+        let creator = UserId::parse("21636369-8b52-9b4a-97b7-50923ceb3ffd").unwrap();
+        user.not_interested_creators = vec![creator];
+        assert!(!user.downvoted_videos.is_empty());
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let videos = next_videos(&user, &config, &store, &indexes, &HashSet::new(), &mut rng)
+                .await
+                .unwrap();
+            assert!(!videos.is_empty());
+            assert!(!videos.iter().any(|video| user.rejects(video)));
+        }
+    }
 
-fn normalize_score(score: &mut f64, target: &f64, threshold: f64) {
-    let threshold = threshold.max(0.0).min(1.0);
-    let ratio = *score / target;
+    #[tokio::test]
+    async fn unknown_users_get_cold_start_videos() {
+        let config = config();
+        let (store, indexes) = fixture().await;
+        let user = fixture_user(&store, "00000000-0000-0000-0000-000000000000", &config).await;
+        assert_eq!(personalization_level(&user, &config), 0.);
+
+        let (popular, trending) = store.fetch_cold_start_videos(&config).await.unwrap();
+        let popular: HashSet<VideoId> = popular.into_iter().map(|video| video.uuid).collect();
+        for seed in 0..20 {
+            let videos = next_video_ids(&user, &config, &store, &indexes, seed).await;
+            assert!(!videos.is_empty());
+            assert!(videos.len() <= config.selecting.max_next_videos_amount as usize);
+            // Everything that is neither popular nor trending is a diverse hashtag video
+            let is_trending = |id: &VideoId| trending.iter().any(|video| video.uuid == *id);
+            let hashtag_videos = videos
+                .iter()
+                .filter(|id| !popular.contains(id) && !is_trending(id))
+                .count();
+            assert!(hashtag_videos <= config.cold_start.diverse_hashtag_fetch_amount as usize);
+            assert!(videos.iter().any(|id| popular.contains(id)));
+        }
+    }
 
-    if ratio > 1.0 {
-        *score = target * (1.0 + (ratio - 1.0) * (1.0 - threshold));
-    } else {
-        *score = target * (1.0 - (1.0 - ratio) * (1.0 - threshold));
+    fn weighted(hashtags: &[(&str, f64)]) -> Vec<(String, f64)> {
+        hashtags
+            .iter()
+            .map(|(hashtag, weight)| (hashtag.to_string(), *weight))
+            .collect()
     }
-}
 
-pub fn score_video_personalized(user: &User, video: &Video, config: &Config) -> f64 {
-    let mut score = score_video(video, config);
-    if user.following.contains(&video.user_id) {
-        score *= config.scoring.viewer_following_creator_multiplier;
+    #[test]
+    fn hashtag_quotas_add_up_to_the_fetch_amount() {
+        let hashtags = weighted(&[("a", 5.), ("b", 3.), ("c", 1.), ("d", 0.1)]);
+        let quotas = hashtag_quotas(&hashtags, 10);
+        assert_eq!(quotas.iter().map(|(_, quota)| quota).sum::<u32>(), 10);
+        assert!(quotas.iter().all(|(_, quota)| *quota >= 1));
+        assert!(quotas.windows(2).all(|pair| pair[0].1 >= pair[1].1));
     }
 
-    //Do not wanna show exact same videos
-    if user.liked_videos.contains(&video.uuid) {
-        score *= config.scoring.viewer_liked_video_multiplier;
+    #[test]
+    fn hashtag_quotas_keep_the_heaviest_hashtags_if_there_are_more_than_videos() {
+        let hashtags = weighted(&[("a", 1.), ("b", 4.), ("c", 2.), ("d", 3.)]);
+        let quotas = hashtag_quotas(&hashtags, 2);
+        assert_eq!(quotas, vec![("b".to_string(), 1), ("d".to_string(), 1)]);
     }
 
-    score
+    #[tokio::test]
+    async fn removed_videos_are_neither_sampled_nor_scored() {
+        let config = config();
+        let (_store, indexes) = fixture().await;
+        let removed = {
+            let candidates = indexes.candidates.read().unwrap();
+            *indexes.scores.write().unwrap() = ScoreIndex::build(candidates.videos(), &config);
+            candidates
+                .videos()
+                .iter()
+                .find(|video| !video.hashtags.is_empty())
+                .unwrap()
+                .clone()
+        };
+        let pool_size = indexes.candidates.read().unwrap().len();
+
+        assert!(indexes.remove_video(&removed.uuid));
+        assert!(!indexes.remove_video(&removed.uuid));
+        assert!(indexes.scores.read().unwrap().get(&removed.uuid, &config).is_none());
+
+        let candidates = indexes.candidates.read().unwrap();
+        assert_eq!(candidates.len(), pool_size - 1);
+        let quotas: Vec<(String, u32)> = removed
+            .hashtags
+            .iter()
+            .map(|hashtag| (hashtag.clone(), u32::MAX))
+            .collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(candidates
+            .hashtag_videos(&quotas, &mut rng)
+            .iter()
+            .all(|video| video.uuid != removed.uuid));
+    }
 }
@@ -42,7 +42,7 @@ pub fn gen_token(username: String) -> Result<String, Error> {
 
 pub fn extract_claims(token: &str) -> Result<Claims, Error> {
     let claims = decode::<Claims>(
-        &token,
+        token,
         &DecodingKey::from_base64_secret(
             env::var(JWT_SECRET_KEY)
                 .expect("Failed to get jwt secret")
@@ -63,7 +63,7 @@ pub struct Claims {
     pub exp: usize,
 }
 
-fn extract_auth_header<'a>(request: &'a Request<Body>) -> Option<&'a str> {
+fn extract_auth_header(request: &Request<Body>) -> Option<&str> {
     request
         .headers()
         .get(AUTHORIZATION)
@@ -0,0 +1,87 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::{seq::index, Rng};
+
+use crate::database::{Video, VideoId};
+
+/// The newest ready videos with their stats, refreshed in the background. Random and
+/// hashtag candidates are sampled from here, so selecting the next videos does
+/// not need to scan the video table (`ORDER BY RAND()`) on every request.
+#[derive(Default)]
+pub struct CandidatePool {
+    videos: Vec<Video>,
+
+    /// The positions of the videos of every hashtag (inverted index), so the
+    /// videos matching a hashtag are found without scanning all videos.
+    hashtag_videos: HashMap<String, Vec<usize>>,
+}
+
+impl CandidatePool {
+    pub fn build(videos: Vec<Video>) -> Self {
+        let mut hashtag_videos: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, video) in videos.iter().enumerate() {
+            let hashtags: HashSet<&String> = video.hashtags.iter().collect();
+            for hashtag in hashtags {
+                hashtag_videos.entry(hashtag.clone()).or_default().push(i);
+            }
+        }
+
+        Self {
+            videos,
+            hashtag_videos,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.videos.len()
+    }
+
+    /// All videos of the pool, the newest first.
+    pub fn videos(&self) -> &[Video] {
+        &self.videos
+    }
+
+    /// Removes the video, returns if it was in the pool. The positions of the
+    /// following videos change, so the hashtag index is built again.
+    pub fn remove(&mut self, uuid: &VideoId) -> bool {
+        let Some(position) = self.videos.iter().position(|video| video.uuid == *uuid) else {
+            return false;
+        };
+
+        let mut videos = std::mem::take(&mut self.videos);
+        videos.remove(position);
+        *self = Self::build(videos);
+        true
+    }
+
+    /// Samples up to `amount` random videos.
+    pub fn random_videos(&self, amount: u32, rng: &mut impl Rng) -> Vec<Video> {
+        let amount = (amount as usize).min(self.videos.len());
+        index::sample(rng, self.videos.len(), amount)
+            .into_iter()
+            .map(|i| self.videos[i].clone())
+            .collect()
+    }
+
+    /// Samples random videos of every hashtag, each hashtag with its own
+    /// limit (quota). Videos matching multiple hashtags are only returned once.
+    pub fn hashtag_videos(&self, hashtags: &[(String, u32)], rng: &mut impl Rng) -> Vec<Video> {
+        let mut sampled: HashSet<usize> = HashSet::new();
+        let mut videos = Vec::new();
+        for (hashtag, quota) in hashtags {
+            let Some(hashtag_videos) = self.hashtag_videos.get(hashtag) else {
+                continue;
+            };
+
+            let amount = (*quota as usize).min(hashtag_videos.len());
+            for i in index::sample(rng, hashtag_videos.len(), amount) {
+                let video = hashtag_videos[i];
+                if sampled.insert(video) {
+                    videos.push(self.videos[video].clone());
+                }
+            }
+        }
+
+        videos
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashSet,
     fs::{self, File},
     io::{Error, Write},
 };
@@ -10,14 +11,18 @@ use figment::{
 use log::info;
 use serde::{Deserialize, Serialize};
 
+use crate::{experiment, scoring};
+
 const FILE_PATH: &str = "config.json";
 pub const JWT_SECRET_KEY: &str = "JWT_SECRET";
 pub const INTERNAL_SECRET_KEY: &str = "INTERNAL_SECRET";
 pub const DATABASE_CONN_URL_KEY: &str = "DATABASE_CONNECTION_URL";
+pub const DATABASE_REPLICA_CONN_URLS_KEY: &str = "DATABASE_REPLICA_CONNECTION_URLS"; //Optional, comma separated read replicas
+pub const FIXTURE_PATH_KEY: &str = "FIXTURE_PATH"; //If set, videos and users are loaded from this file instead of the database
 pub const HOST_IP_KEY: &str = "HOST_IP";
 pub const HOST_PORT_KEY: &str = "SERVER_PORT";
 
-// Tables
+// Tables (defaults of the schema config)
 pub const DB_VIDEO_TABLE: &str = "video";
 pub const DB_LIKED_VIDEOS_TABLE: &str = "liked_videos";
 pub const DB_VIEWED_VIDEOS_TABLE: &str = "viewed_video";
@@ -25,9 +30,14 @@ pub const DB_VIEWED_VIDEOS_TABLE: &str = "viewed_video";
 pub const DB_USER_FOLLOWED_USER_TABLE: &str = "user_followed_users";
 pub const VIDEO_READY_STATUS: &str = "3";
 pub const DB_USER_LIKED_HASHTAGS_TABLE: &str = "user_liked_hastags";
+pub const DB_DOWNVOTED_VIDEOS_TABLE: &str = "disliked_videos";
+pub const DB_USER_NOT_INTERESTED_HASHTAGS_TABLE: &str = "user_not_interested_hashtags";
+pub const DB_USER_NOT_INTERESTED_USERS_TABLE: &str = "user_not_interested_users";
+pub const DB_VIDEO_HASHTAGS_TABLE: &str = "video_hashtags";
 
-// Columns
+// Columns (defaults of the schema config)
 pub const VIEWED_AT_COLUMN: &str = "viewed_at";
+pub const LIKED_AT_COLUMN: &str = "liked_at";
 pub const USER_ID_COLUMN: &str = "user_id";
 pub const VIDEO_STATUS_COLUMN: &str = "status";
 pub const FOLLOWED_USERS_COLUMN: &str = "followed_users";
@@ -39,7 +49,11 @@ pub const VIDEO_HASHTAGS_COLUMN: &str = "hashtags";
 pub const VIDEO_DOWN_VOTES_COLUMN: &str = "down_votes";
 pub const VIDEO_VIEWS_COLUMN: &str = "views";
 pub const VIDEO_VIEWTIME_COLUMN: &str = "viewtime_seconds";
+pub const VIDEO_CREATED_AT_COLUMN: &str = "created_at";
 pub const TIMESTAMP_COLUMN: &str = "timestamp";
+pub const NOT_INTERESTED_HASHTAG_COLUMN: &str = "hashtag";
+pub const VIDEO_HASHTAG_COLUMN: &str = "hashtag";
+pub const NOT_INTERESTED_USERS_COLUMN: &str = "not_interested_users";
 
 pub fn load() -> Config {
     let config = Figment::new().merge(Json::file(FILE_PATH));
@@ -57,6 +71,7 @@ pub fn overwrite(content: String) -> Result<(), Error> {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(true)
         .open(FILE_PATH)
         .unwrap();
 
@@ -73,10 +88,96 @@ pub fn validate(c: &Config) -> Result<(), &str> {
         return Err("Next Video amount must be higher than the Next Video Fetch Amount!");
     }
 
+    if scoring::by_name(&c.scoring.scorer).is_none() {
+        return Err("Unknown scorer, see scoring.rs for the available scorers");
+    }
+
+    if !(0. ..=1.).contains(&c.selecting.exploration_rate) {
+        return Err("Exploration rate must be between 0.00 and 1.00");
+    }
+
+    if !(0. ..=1.).contains(&c.selecting.related_video_probability) {
+        return Err("Related video probability must be between 0.00 and 1.00");
+    }
+
+    if c.collaborative.rebuild_interval_seconds == 0 {
+        return Err("Co-like index rebuild interval must be higher than 0");
+    }
+
+    if c.hashtag_graph.rebuild_interval_seconds == 0 {
+        return Err("Hashtag graph rebuild interval must be higher than 0");
+    }
+
+    if c.selecting.hashtag_expansion_weight < 0. {
+        return Err("Hashtag expansion weight can not be negative");
+    }
+
+    if !(0. ..=1.).contains(&c.selecting.trending_video_probability) {
+        return Err("Trending video probability must be between 0.00 and 1.00");
+    }
+
+    if c.trending.rebuild_interval_seconds == 0 {
+        return Err("Trending list rebuild interval must be higher than 0");
+    }
+
+    if c.trending.short_window_seconds == 0
+        || c.trending.long_window_seconds <= c.trending.short_window_seconds
+    {
+        return Err("Trending long window must be longer than the short window (higher than 0)");
+    }
+
+    if c.replicas.health_check_interval_seconds == 0 {
+        return Err("Replica health check interval must be higher than 0");
+    }
+
+    if c.replicas.timeout_millis == 0 {
+        return Err("Replica timeout must be higher than 0");
+    }
+
+    for (table, columns) in c.schema.tables() {
+        if !is_identifier(table) || !columns.into_iter().all(is_identifier) {
+            return Err("Schema names must be plain identifiers ([A-Za-z_][A-Za-z0-9_]*)");
+        }
+    }
+
+    if c.candidate_pool.refresh_interval_seconds == 0 {
+        return Err("Candidate pool refresh interval must be higher than 0");
+    }
+
+    if c.score_index.rebuild_interval_seconds == 0 {
+        return Err("Score index rebuild interval must be higher than 0");
+    }
+
+    if c.selecting.blended_hashtags_amount == 0 {
+        return Err("Blended hashtags amount must be higher than 0");
+    }
+
+    if c.selecting.max_videos_per_creator == 0 {
+        return Err("Max videos per creator must be higher than 0");
+    }
+
     if c.scoring.viral_score <= 0. {
         return Err("Viral Score must be higher than 0.00");
     }
 
+    if c.scoring.freshness_half_life_hours < 0. {
+        return Err("Freshness half life can not be negative");
+    }
+
+    if c.scoring.prior_strength <= 0. {
+        return Err("Prior strength must be higher than 0.00");
+    }
+
+    if !(0. ..=1.).contains(&c.scoring.prior_like_2_view_ratio)
+        || !(0. ..=1.).contains(&c.scoring.prior_upvote_2_totalvotes_ratio)
+    {
+        return Err("Prior like 2 view and upvote 2 totalvotes ratios must be between 0.00 and 1.00");
+    }
+
+    if c.scoring.prior_viewtime_per_view < 0. || c.scoring.prior_comments_2_votes_ratio < 0. {
+        return Err("Prior viewtime per view and comments 2 votes ratio can not be negative");
+    }
+
     if c.selecting.high_score_video_probability <= 0. {
         return Err("High Score Prob. must be higher than 0.00");
     }
@@ -97,12 +198,65 @@ pub fn validate(c: &Config) -> Result<(), &str> {
         return Err("Upvotes 2 totalvotes strength must be higher than 0.00");
     }
 
+
+    let mut experiment_names = HashSet::new();
+    for experiment in &c.experiments {
+        if experiment.name == experiment::CONTROL_VARIANT
+            || !experiment_names.insert(&experiment.name)
+        {
+            return Err("Experiment names must be unique and can not be \"control\"");
+        }
+
+        if !experiment.treatment.experiments.is_empty() {
+            return Err("Experiment treatments can not have experiments themselves");
+        }
+
+        if experiment.treatment.schema != c.schema {
+            return Err("Experiment treatments can not change the schema");
+        }
+
+        if experiment.treatment.replicas != c.replicas {
+            return Err("Experiment treatments can not change the replica policy");
+        }
+
+        let treatment = &experiment.treatment;
+        if treatment.trending != c.trending
+            || treatment.candidate_pool != c.candidate_pool
+            || treatment.collaborative != c.collaborative
+            || treatment.hashtag_graph != c.hashtag_graph
+        {
+            return Err("Experiment treatments can not change the indexes, they are shared");
+        }
+
+        validate(&experiment.treatment)?;
+    }
+
+    let mut buckets: Vec<(f64, f64)> = c
+        .experiments
+        .iter()
+        .map(|experiment| {
+            let start = experiment.bucket_start;
+            (start, start + experiment.traffic_share)
+        })
+        .collect();
+    buckets.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    if buckets.iter().any(|(start, end)| !(*start >= 0. && start <= end && *end <= 1.))
+        || buckets.windows(2).any(|pair| pair[1].0 < pair[0].1)
+    {
+        return Err("Experiment bucket ranges must be within 0.00 and 1.00 and can not overlap");
+    }
+
     Ok(())
 }
 
-#[derive(Deserialize, Serialize, Clone)]
-#[serde(rename_all = "camelCase")]
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
 pub struct ScoringConfig {
+    /// The name of the scoring strategy that is used to score videos.
+    /// The values underneath are used by the strategy, see `scoring.rs`
+    /// for the available strategies. Currently: "default" and "bayesian".
+    pub scorer: String,
+
     /// The score increases by being multiplied with the upvotes to the power of x.
     /// This value controlls x. Which means: the higher this value is the more it is
     /// going to increase the score, or the upvotes strength is higher.
@@ -150,14 +304,79 @@ pub struct ScoringConfig {
     /// that are already watched again, so this should normally be under 1.0
     pub viewer_liked_video_multiplier: f64,
 
+    /// If the user downvoted the video this value gets multiplied with
+    /// the pscore. Downvoted videos are not selected for the next videos anyway,
+    /// this mostly affects the personalized score endpoint. Should be under 1.0
+    pub viewer_downvoted_video_multiplier: f64,
+
+    /// If the video has a hashtag the user marked as "not interested", this
+    /// value gets multiplied with the pscore (once, not per hashtag). Should be under 1.0
+    pub viewer_not_interested_hashtag_multiplier: f64,
+
+    /// If the user marked the video creator as "not interested", this value gets
+    /// multiplied with the pscore. Videos of these creators are not selected for
+    /// the next videos anyway. Should be under 1.0
+    pub viewer_not_interested_creator_multiplier: f64,
+
     /// This value is used as a reference for a score that maps to a "viral"
     /// video. This is just used for the normalization process, it does not directly
     /// influence the scoring.
     pub viral_score: f64,
+
+    /// The age in hours after which a video only keeps half of its score.
+    /// After two half-lifes it keeps a quarter and so on. This makes new
+    /// videos surface instead of old viral ones. 0.0 disables the decay.
+    pub freshness_half_life_hours: f64,
+
+    /// BAYESIAN SCORER:
+    /// The amount of imaginary views/votes every video starts with. These have the
+    /// prior ratios underneath. The higher this value, the more real stats a video needs
+    /// until its own ratios outweigh the priors, so videos with few views and a lucky
+    /// perfect ratio do not beat established videos.
+    pub prior_strength: f64,
+
+    /// The like to view ratio a video without any stats is assumed to have.
+    pub prior_like_2_view_ratio: f64,
+
+    /// The upvotes to total votes ratio a video without any votes is assumed to have.
+    pub prior_upvote_2_totalvotes_ratio: f64,
+
+    /// The average viewtime per view (in seconds) a video without views is assumed to have.
+    pub prior_viewtime_per_view: f64,
+
+    /// The comments to votes ratio a video without any votes is assumed to have.
+    pub prior_comments_2_votes_ratio: f64,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            scorer: scoring::DEFAULT_SCORER.to_string(),
+            upvote_exponent: 4.0,
+            view_exponent: 3.5,
+            like_2_view_strength: 1.2,
+            viewtime_per_view_strength: 1.2,
+            comments_2_votes_strength: 0.9,
+            upvote_2_totalvotes_strength: 1.8,
+            normalize_threshold: 0.65,
+            viewer_following_creator_multiplier: 1.03,
+            viewer_liked_video_multiplier: 0.96,
+            viewer_downvoted_video_multiplier: 0.3,
+            viewer_not_interested_hashtag_multiplier: 0.5,
+            viewer_not_interested_creator_multiplier: 0.2,
+            viral_score: 1000.0,
+            freshness_half_life_hours: 72.0,
+            prior_strength: 20.0,
+            prior_like_2_view_ratio: 0.1,
+            prior_upvote_2_totalvotes_ratio: 0.8,
+            prior_viewtime_per_view: 10.0,
+            prior_comments_2_votes_ratio: 0.05,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone)]
-#[serde(rename_all = "camelCase")]
+#[serde(default, rename_all = "camelCase")]
 pub struct SelectingConfig {
     /// The algorithm fetches the hashtags of the videos the user
     /// liked and sorts them so the most frequent hashtags are
@@ -169,6 +388,17 @@ pub struct SelectingConfig {
     /// so a hashtag with a high repeat rate.
     pub select_high_freq_hashtag_probability: f64,
 
+    /// How many different hashtags of the user are selected (with the probability above)
+    /// for one next videos batch. The hashtag video fetch amount is split between them
+    /// proportional to how often the user liked each of them.
+    pub blended_hashtags_amount: u32,
+
+    /// The selected hashtags are expanded to their related hashtags (see hashtag graph).
+    /// A related hashtag gets the weight of the selected hashtag multiplied with their
+    /// similarity and this value, the fetch amount is split by these weights.
+    /// 0.0 disables the expansion.
+    pub hashtag_expansion_weight: f64,
+
     /// This is how many liked videos should be
     /// fetched for hashtag analytics. This should be
     /// way more than actual hashtag video fetch amount.
@@ -215,6 +445,580 @@ pub struct SelectingConfig {
     /// fetched for checking if a video repeats on a users
     /// for you.
     pub already_viewed_videos_fetch_amount: u32,
+
+    /// The max. amount of videos of the same creator in one next videos batch.
+    /// If a creator dominates a hashtag the other slots are filled with
+    /// the remaining fetched videos.
+    pub max_videos_per_creator: u32,
+
+    /// The min. amount of other videos between two videos of the same
+    /// creator in one next videos batch.
+    pub min_same_creator_gap: u32,
+
+    /// How long (in seconds) a feed session is remembered after its last
+    /// next videos request. Videos served in a session are not served again
+    /// in the same session.
+    pub feed_session_ttl_seconds: u64,
+
+    /// The probability that a slot in the next videos is used to explore a fresh
+    /// video (one with few views). These videos would score low because their stats
+    /// are not reliable yet, the exploration ranks them by how promising they are instead.
+    pub exploration_rate: f64,
+
+    /// Videos with less views than this count as fresh and can be explored. This is the
+    /// exposure budget of a new video, after that it has to compete by its score.
+    pub exploration_max_views: u32,
+
+    /// How many fresh videos (newest first) are fetched for the exploration.
+    pub exploration_fetch_amount: u32,
+
+    /// The probability that a slot in the next videos is filled with a video related to
+    /// the videos the user liked (liked by the same users). Related videos are used
+    /// best first and only if there are any.
+    pub related_video_probability: f64,
+
+    /// The probability that a slot in the next videos is filled with a trending
+    /// video (see trending config). Trending videos are used most trending first.
+    pub trending_video_probability: f64,
+}
+
+impl Default for SelectingConfig {
+    fn default() -> Self {
+        Self {
+            select_high_freq_hashtag_probability: 0.65,
+            blended_hashtags_amount: 3,
+            hashtag_expansion_weight: 0.5,
+            user_hashtag_fetch_amount: 100,
+            hashtag_2_random_video_probability: 0.8,
+            high_score_video_probability: 0.25,
+            high_score_after_hashtag_video_probability: 0.2,
+            max_next_videos_amount: 15,
+            next_videos_fetch_amount_matching_hashtag: 10,
+            next_videos_fetch_amount_random: 15,
+            already_watched_video_sort_out_probability: 0.45,
+            already_viewed_videos_fetch_amount: 12,
+            max_videos_per_creator: 3,
+            min_same_creator_gap: 2,
+            feed_session_ttl_seconds: 1800,
+            exploration_rate: 0.1,
+            exploration_max_views: 200,
+            exploration_fetch_amount: 10,
+            related_video_probability: 0.2,
+            trending_video_probability: 0.1,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ColdStartConfig {
+    /// The amount of interactions (liked videos, followed users and viewed videos)
+    /// a user needs until the feed is fully personalized. Until then, a part of every
+    /// next videos batch are cold start videos. This part shrinks with every interaction,
+    /// a user without any interactions gets only cold start videos.
+    pub full_personalization_interactions: u32,
+
+    /// How many of the globally most upvoted videos are fetched for the cold start.
+    pub popular_fetch_amount: u32,
+
+    /// How many of the most viewed recently uploaded videos are fetched for the cold start.
+    pub trending_fetch_amount: u32,
+
+    /// Videos uploaded within this amount of hours count as recently uploaded.
+    pub trending_max_age_hours: u32,
+
+    /// How many random videos are fetched for the cold start. From these, only
+    /// videos with hashtags that are not covered yet are used, so the new user
+    /// sees as many different hashtags as possible.
+    pub diverse_hashtag_fetch_amount: u32,
+}
+
+impl Default for ColdStartConfig {
+    fn default() -> Self {
+        Self {
+            full_personalization_interactions: 20,
+            popular_fetch_amount: 10,
+            trending_fetch_amount: 10,
+            trending_max_age_hours: 48,
+            diverse_hashtag_fetch_amount: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CollaborativeConfig {
+    /// The seconds between two rebuilds of the co-like index (videos liked
+    /// by the same users are related). The index is built in the background.
+    pub rebuild_interval_seconds: u64,
+
+    /// The max. amount of likes that are loaded to build the index.
+    pub max_loaded_likes: u32,
+
+    /// Only this many likes per user are used for the index. Every pair of liked
+    /// videos is counted, so this bounds the build time per user.
+    pub max_likes_per_user: u32,
+
+    /// How many of the most related videos are kept per video.
+    pub related_videos_per_video: u32,
+
+    /// How many videos related to the users liked videos are fetched
+    /// for the next videos algorithm.
+    pub related_fetch_amount: u32,
+}
+
+impl Default for CollaborativeConfig {
+    fn default() -> Self {
+        Self {
+            rebuild_interval_seconds: 900,
+            max_loaded_likes: 500000,
+            max_likes_per_user: 50,
+            related_videos_per_video: 20,
+            related_fetch_amount: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HashtagGraphConfig {
+    /// The seconds between two rebuilds of the hashtag graph (hashtags used
+    /// on the same videos are related). The graph is built in the background.
+    pub rebuild_interval_seconds: u64,
+
+    /// The max. amount of videos whose hashtags are loaded to build the graph.
+    pub max_loaded_videos: u32,
+
+    /// How many of the most related hashtags are kept per hashtag. These are
+    /// the hashtags a selected user hashtag is expanded to.
+    pub related_hashtags_per_hashtag: u32,
+}
+
+impl Default for HashtagGraphConfig {
+    fn default() -> Self {
+        Self {
+            rebuild_interval_seconds: 1800,
+            max_loaded_videos: 100000,
+            related_hashtags_per_hashtag: 2,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TrendingConfig {
+    /// The seconds between two rebuilds of the trending list. The list is built
+    /// in the background from the view and like timestamps.
+    pub rebuild_interval_seconds: u64,
+
+    /// The views and likes within this window (in seconds, until now) make
+    /// up the current velocity of a video.
+    pub short_window_seconds: u64,
+
+    /// The views and likes within this window (in seconds, until now) make up the
+    /// usual velocity of a video. A video is trending if its current velocity is higher.
+    /// This needs to be longer than the short window.
+    pub long_window_seconds: u64,
+
+    /// A like counts as this many views for the velocity.
+    pub like_weight: f64,
+
+    /// Videos with less views and likes in the short window are not trending,
+    /// a few views on a video without history would be a huge acceleration.
+    pub min_short_window_events: u32,
+
+    /// The max. amount of videos in the trending list.
+    pub list_size: u32,
+
+    /// How many of the most trending videos are fetched for the next videos algorithm.
+    pub candidate_amount: u32,
+}
+
+impl Default for TrendingConfig {
+    fn default() -> Self {
+        Self {
+            rebuild_interval_seconds: 300,
+            short_window_seconds: 21600,
+            long_window_seconds: 604800,
+            like_weight: 3.0,
+            min_short_window_events: 10,
+            list_size: 100,
+            candidate_amount: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CandidatePoolConfig {
+    /// The seconds between two refreshes of the candidate pool. The pool holds the
+    /// newest ready videos, random and hashtag videos are sampled from it.
+    pub refresh_interval_seconds: u64,
+
+    /// The max. amount of ready videos that are loaded into the pool (the newest ones).
+    pub max_loaded_videos: u32,
+}
+
+impl Default for CandidatePoolConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval_seconds: 300,
+            max_loaded_videos: 200000,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScoreIndexConfig {
+    /// The seconds between two rebuilds of the score index. The index holds the
+    /// precomputed scores of the candidate pool videos, so they do not need to be
+    /// scored on every request. It is also rebuilt when the scoring config is updated
+    /// and after every refresh of the candidate pool.
+    pub rebuild_interval_seconds: u64,
+
+    /// The max. amount of candidate pool videos that are scored.
+    pub max_loaded_videos: u32,
+}
+
+impl Default for ScoreIndexConfig {
+    fn default() -> Self {
+        Self {
+            rebuild_interval_seconds: 600,
+            max_loaded_videos: 200000,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UserCacheConfig {
+    /// The seconds a fetched user profile is reused. Profiles are invalidated
+    /// explicitly when the user likes or follows something, so this only bounds
+    /// how outdated e.g. the last viewed videos can be.
+    pub ttl_seconds: u64,
+
+    /// The max. amount of cached users, the least recently used user is evicted
+    /// when the cache is full. 0 disables the cache.
+    pub max_users: u32,
+}
+
+impl Default for UserCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_seconds: 120,
+            max_users: 50000,
+        }
+    }
+}
+
+/// Which reads are sent to the read replicas (if replica connection urls are set),
+/// so the feed traffic does not load the primary database the main backend uses.
+/// A replica that can not be reached is skipped until a health check succeeds again,
+/// its reads go to the other replicas or, if none is healthy, to the primary.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ReplicaConfig {
+    /// The video reads: candidates of the feed, the background indexes and scored videos.
+    pub candidate_reads: bool,
+
+    /// The user profiles (likes, follows, hashtags etc.). Off by default: replicas lag
+    /// behind the primary, so a profile read right after an invalidation can miss the
+    /// newest likes and the outdated profile would be cached for the whole user cache TTL.
+    pub profile_reads: bool,
+
+    /// The seconds between two health checks of the replicas.
+    pub health_check_interval_seconds: u64,
+
+    /// The max. milliseconds a replica has to answer a read, a health check or to hand
+    /// out a connection. A replica that is slower is marked unhealthy and the read is
+    /// repeated on the primary.
+    pub timeout_millis: u64,
+}
+
+impl Default for ReplicaConfig {
+    fn default() -> Self {
+        Self {
+            candidate_reads: true,
+            profile_reads: false,
+            health_check_interval_seconds: 10,
+            timeout_millis: 2000,
+        }
+    }
+}
+
+/// Defines the config of a table: its name and the names of the read columns, each
+/// with its default. Every table has its own type, so a table section that only
+/// renames some columns keeps the defaults of that table for the others.
+macro_rules! table_config {
+    ($(#[$meta:meta])* $name:ident { $($field:ident: $default:expr),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Deserialize, Serialize, Clone, PartialEq)]
+        #[serde(default, rename_all = "camelCase")]
+        pub struct $name {
+            $(pub $field: String,)*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self {
+                    $($field: $default.to_string(),)*
+                }
+            }
+        }
+    };
+}
+
+table_config!(
+    /// The videos with their stats and hashtags (a JSON array).
+    VideoTableConfig {
+        table: DB_VIDEO_TABLE,
+        uuid_column: UUID_COLUMN,
+        user_id_column: USER_ID_COLUMN,
+        status_column: VIDEO_STATUS_COLUMN,
+        comments_column: VIDEO_COMMENTS_COLUMN,
+        up_votes_column: VIDEO_UP_VOTES_COLUMN,
+        down_votes_column: VIDEO_DOWN_VOTES_COLUMN,
+        views_column: VIDEO_VIEWS_COLUMN,
+        viewtime_column: VIDEO_VIEWTIME_COLUMN,
+        created_at_column: VIDEO_CREATED_AT_COLUMN,
+        hashtags_column: VIDEO_HASHTAGS_COLUMN,
+    }
+);
+
+table_config!(
+    /// One row per like of a video.
+    LikedVideosTableConfig {
+        table: DB_LIKED_VIDEOS_TABLE,
+        user_id_column: USER_ID_COLUMN,
+        video_id_column: VIDEO_ID_COLUMN,
+        liked_at_column: LIKED_AT_COLUMN,
+    }
+);
+
+table_config!(
+    /// One row per view of a video.
+    ViewedVideosTableConfig {
+        table: DB_VIEWED_VIDEOS_TABLE,
+        user_id_column: USER_ID_COLUMN,
+        video_id_column: VIDEO_ID_COLUMN,
+        viewed_at_column: VIEWED_AT_COLUMN,
+    }
+);
+
+table_config!(
+    /// One row per followed user.
+    FollowedUsersTableConfig {
+        table: DB_USER_FOLLOWED_USER_TABLE,
+        user_id_column: USER_ID_COLUMN,
+        followed_users_column: FOLLOWED_USERS_COLUMN,
+    }
+);
+
+table_config!(
+    /// One row (with the hashtags as JSON array) per liked video.
+    LikedHashtagsTableConfig {
+        table: DB_USER_LIKED_HASHTAGS_TABLE,
+        user_id_column: USER_ID_COLUMN,
+        hashtags_column: VIDEO_HASHTAGS_COLUMN,
+        timestamp_column: TIMESTAMP_COLUMN,
+    }
+);
+
+table_config!(
+    /// One row per downvoted video.
+    DownvotedVideosTableConfig {
+        table: DB_DOWNVOTED_VIDEOS_TABLE,
+        user_id_column: USER_ID_COLUMN,
+        video_id_column: VIDEO_ID_COLUMN,
+    }
+);
+
+table_config!(
+    /// One row per hashtag the user is not interested in.
+    NotInterestedHashtagsTableConfig {
+        table: DB_USER_NOT_INTERESTED_HASHTAGS_TABLE,
+        user_id_column: USER_ID_COLUMN,
+        hashtag_column: NOT_INTERESTED_HASHTAG_COLUMN,
+    }
+);
+
+table_config!(
+    /// One row per creator the user is not interested in.
+    NotInterestedUsersTableConfig {
+        table: DB_USER_NOT_INTERESTED_USERS_TABLE,
+        user_id_column: USER_ID_COLUMN,
+        not_interested_users_column: NOT_INTERESTED_USERS_COLUMN,
+    }
+);
+
+table_config!(
+    /// A normalized table with one (video id, hashtag) row per hashtag of a video.
+    VideoHashtagsTableConfig {
+        table: DB_VIDEO_HASHTAGS_TABLE,
+        video_id_column: VIDEO_ID_COLUMN,
+        hashtag_column: VIDEO_HASHTAG_COLUMN,
+    }
+);
+
+/// The names of the tables and columns in the database, so the service does not
+/// need to be recompiled when the main backend renames them. Every table has its
+/// own section and every name that is not set keeps its default. The schema is
+/// checked against the database on startup and changes only take effect after a restart.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SchemaConfig {
+    pub videos: VideoTableConfig,
+    pub liked_videos: LikedVideosTableConfig,
+    pub viewed_videos: ViewedVideosTableConfig,
+    pub followed_users: FollowedUsersTableConfig,
+    pub liked_hashtags: LikedHashtagsTableConfig,
+    pub downvoted_videos: DownvotedVideosTableConfig,
+    pub not_interested_hashtags: NotInterestedHashtagsTableConfig,
+    pub not_interested_users: NotInterestedUsersTableConfig,
+
+    /// If set, the hashtags of the videos are read from this normalized table instead
+    /// of the JSON column of the video table. It should have an index on the video id column.
+    pub video_hashtags: Option<VideoHashtagsTableConfig>,
+
+    /// The status of videos that are ready to be watched
+    pub ready_status: String,
+}
+
+impl Default for SchemaConfig {
+    fn default() -> Self {
+        Self {
+            videos: VideoTableConfig::default(),
+            liked_videos: LikedVideosTableConfig::default(),
+            viewed_videos: ViewedVideosTableConfig::default(),
+            followed_users: FollowedUsersTableConfig::default(),
+            liked_hashtags: LikedHashtagsTableConfig::default(),
+            downvoted_videos: DownvotedVideosTableConfig::default(),
+            not_interested_hashtags: NotInterestedHashtagsTableConfig::default(),
+            not_interested_users: NotInterestedUsersTableConfig::default(),
+            video_hashtags: None,
+            ready_status: VIDEO_READY_STATUS.to_string(),
+        }
+    }
+}
+
+impl SchemaConfig {
+    /// Every table with the columns that are read from it.
+    pub fn tables(&self) -> Vec<(&str, Vec<&str>)> {
+        let VideoTableConfig {
+            table,
+            uuid_column,
+            user_id_column,
+            status_column,
+            comments_column,
+            up_votes_column,
+            down_votes_column,
+            views_column,
+            viewtime_column,
+            created_at_column,
+            hashtags_column,
+        } = &self.videos;
+        let mut video_columns = vec![
+            uuid_column,
+            user_id_column,
+            status_column,
+            comments_column,
+            up_votes_column,
+            down_votes_column,
+            views_column,
+            viewtime_column,
+            created_at_column,
+        ];
+        if self.video_hashtags.is_none() {
+            video_columns.push(hashtags_column);
+        }
+        let mut tables = vec![(table, video_columns)];
+
+        let LikedVideosTableConfig {
+            table,
+            user_id_column,
+            video_id_column,
+            liked_at_column,
+        } = &self.liked_videos;
+        tables.push((table, vec![user_id_column, video_id_column, liked_at_column]));
+
+        let ViewedVideosTableConfig {
+            table,
+            user_id_column,
+            video_id_column,
+            viewed_at_column,
+        } = &self.viewed_videos;
+        tables.push((table, vec![user_id_column, video_id_column, viewed_at_column]));
+
+        let FollowedUsersTableConfig {
+            table,
+            user_id_column,
+            followed_users_column,
+        } = &self.followed_users;
+        tables.push((table, vec![user_id_column, followed_users_column]));
+
+        let LikedHashtagsTableConfig {
+            table,
+            user_id_column,
+            hashtags_column,
+            timestamp_column,
+        } = &self.liked_hashtags;
+        tables.push((table, vec![user_id_column, hashtags_column, timestamp_column]));
+
+        let DownvotedVideosTableConfig {
+            table,
+            user_id_column,
+            video_id_column,
+        } = &self.downvoted_videos;
+        tables.push((table, vec![user_id_column, video_id_column]));
+
+        let NotInterestedHashtagsTableConfig {
+            table,
+            user_id_column,
+            hashtag_column,
+        } = &self.not_interested_hashtags;
+        tables.push((table, vec![user_id_column, hashtag_column]));
+
+        let NotInterestedUsersTableConfig {
+            table,
+            user_id_column,
+            not_interested_users_column,
+        } = &self.not_interested_users;
+        tables.push((table, vec![user_id_column, not_interested_users_column]));
+
+        if let Some(VideoHashtagsTableConfig {
+            table,
+            video_id_column,
+            hashtag_column,
+        }) = &self.video_hashtags
+        {
+            tables.push((table, vec![video_id_column, hashtag_column]));
+        }
+
+        tables
+            .into_iter()
+            .map(|(table, columns)| {
+                (
+                    table.as_str(),
+                    columns.into_iter().map(String::as_str).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// If the name can be put into SQL as it is: letters, digits and underscores,
+/// not starting with a digit. Names are not quoted, so anything else is rejected.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn default_max_score_batch_size() -> u32 {
+    500
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -222,6 +1026,113 @@ pub struct SelectingConfig {
 pub struct Config {
     pub config_name: String,
     pub max_dbpool_connections: u32,
+
+    /// The max. amount of videos that can be scored with one batch request.
+    #[serde(default = "default_max_score_batch_size")]
+    pub max_score_batch_size: u32,
+
+    // Every section (and every value in it) that is missing in the config
+    // file gets the default, which are the values of the shipped config.json
     pub scoring: ScoringConfig,
     pub selecting: SelectingConfig,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub cold_start: ColdStartConfig,
+    #[serde(default)]
+    pub collaborative: CollaborativeConfig,
+    #[serde(default)]
+    pub hashtag_graph: HashtagGraphConfig,
+    #[serde(default)]
+    pub trending: TrendingConfig,
+    #[serde(default)]
+    pub score_index: ScoreIndexConfig,
+    #[serde(default)]
+    pub candidate_pool: CandidatePoolConfig,
+    #[serde(default)]
+    pub user_cache: UserCacheConfig,
+
+    #[serde(default)]
+    pub schema: SchemaConfig,
+
+    #[serde(default)]
+    pub replicas: ReplicaConfig,
+
+    /// A/B experiments, users are assigned to them by a hash of their user id.
+    /// Users that are in no experiment get this config ("control").
+    #[serde(default)]
+    pub experiments: Vec<ExperimentConfig>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExperimentConfig {
+    /// The variant name, it is returned with the next videos so
+    /// the engagement can be attributed to the experiment.
+    pub name: String,
+
+    /// The share of users (0.0 - 1.0) that get the treatment config. The users of
+    /// the experiment are the ones with a bucket in `[bucket_start, bucket_start + traffic_share)`.
+    pub traffic_share: f64,
+
+    /// Where the bucket range of the experiment starts (0.0 - 1.0). The ranges of the
+    /// experiments can not overlap, users outside of all ranges are the control group.
+    /// The range is fixed, so adding, removing or reordering other experiments does
+    /// not move the users of running experiments to another variant.
+    pub bucket_start: f64,
+
+    /// The complete config the users of this experiment get. The schema, the replica policy
+    /// and the sections of the shared indexes (trending, candidate pool, collaborative and
+    /// hashtag graph) must be the same as in the control config.
+    pub treatment: Box<Config>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(schema: serde_json::Value) -> Config {
+        let mut config: serde_json::Value =
+            serde_json::from_str(include_str!("../config.json")).unwrap();
+        config["schema"] = schema;
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn tables_keep_their_own_defaults() {
+        let config = config(serde_json::json!({
+            "likedVideos": { "videoIdColumn": "liked_video" },
+            "videoHashtags": {}
+        }));
+        let schema = &config.schema;
+        assert_eq!(schema.liked_videos.table, DB_LIKED_VIDEOS_TABLE);
+        assert_eq!(schema.liked_videos.video_id_column, "liked_video");
+        assert_eq!(schema.viewed_videos.video_id_column, VIDEO_ID_COLUMN);
+        assert_eq!(schema.video_hashtags.as_ref().unwrap().table, DB_VIDEO_HASHTAGS_TABLE);
+        assert!(validate(&config).is_ok());
+    }
+
+    #[test]
+    fn names_that_are_no_identifiers_are_rejected() {
+        for name in ["video; DROP TABLE video", "1video", "video table", ""] {
+            let config = config(serde_json::json!({ "videos": { "table": name } }));
+            assert!(validate(&config).is_err(), "{name:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn treatments_that_change_the_shared_indexes_are_rejected() {
+        let control = config(serde_json::json!({}));
+        let mut treatment = control.clone();
+        treatment.scoring.upvote_exponent += 0.1;
+        let mut config = control.clone();
+        config.experiments = vec![ExperimentConfig {
+            name: "fresher".to_string(),
+            traffic_share: 0.5,
+            bucket_start: 0.0,
+            treatment: Box::new(treatment),
+        }];
+        assert!(validate(&config).is_ok());
+
+        config.experiments[0].treatment.trending.list_size += 1;
+        assert!(validate(&config).is_err());
+    }
+}
@@ -0,0 +1,989 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    error::BoxDynError, pool::PoolOptions, query, query_as, query_scalar, types::Json, ColumnIndex,
+    Database, Decode, Encode, Executor, FromRow, IntoArguments, Pool, Row, Type,
+};
+use uuid::Uuid;
+
+use crate::{
+    config::{
+        Config, LikedHashtagsTableConfig, LikedVideosTableConfig, SchemaConfig,
+        VideoHashtagsTableConfig, VideoTableConfig, ViewedVideosTableConfig,
+    },
+    store::{self, Store, StoreFuture, UserStore, VideoStore},
+    trending::WindowCounts,
+};
+
+#[cfg(feature = "mysql")]
+pub mod mysql;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+compile_error!("At least one database feature (mysql, postgres or sqlite) has to be enabled");
+
+/// Defines an id newtype. Ids are uuids in the format the databases return them
+/// (lowercase, hyphenated), so two ids of the same video are always equal.
+/// Ids from requests and from rows are parsed (and normalized) the same way, a row
+/// with an id that is no uuid can not be decoded.
+macro_rules! uuid_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+        #[serde(try_from = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn parse(id: &str) -> Result<Self, uuid::Error> {
+                Ok(Self(Uuid::parse_str(id)?.hyphenated().to_string()))
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = uuid::Error;
+
+            fn try_from(id: String) -> Result<Self, Self::Error> {
+                Self::parse(&id)
+            }
+        }
+
+        impl<DB: Database> Type<DB> for $name
+        where
+            String: Type<DB>,
+        {
+            fn type_info() -> DB::TypeInfo {
+                <String as Type<DB>>::type_info()
+            }
+
+            fn compatible(ty: &DB::TypeInfo) -> bool {
+                <String as Type<DB>>::compatible(ty)
+            }
+        }
+
+        impl<'r, DB: Database> Decode<'r, DB> for $name
+        where
+            String: Decode<'r, DB>,
+        {
+            fn decode(value: DB::ValueRef<'r>) -> Result<Self, BoxDynError> {
+                Ok(Self::parse(&String::decode(value)?)?)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+uuid_id!(
+    /// The id (uuid) of a video.
+    VideoId
+);
+
+uuid_id!(
+    /// The id (uuid) of a user, also of the creator of a video.
+    UserId
+);
+
+/// A user without any rows (e.g. a new user) has no interactions, so
+/// every user id has a user and an unknown one gets the cold start.
+#[derive(Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub liked_videos: Vec<VideoId>,
+    pub following: Vec<UserId>,
+    pub last_hashtags: Vec<String>, //Last liked hashtag, filtered by timestamp
+    pub last_viewed: Vec<VideoId>,
+
+    // Negative signals
+    pub downvoted_videos: Vec<VideoId>,
+    pub not_interested_hashtags: Vec<String>,
+    pub not_interested_creators: Vec<UserId>,
+}
+
+impl User {
+    /// If the user downvoted the video or is not interested in its creator,
+    /// it should not be part of the users feed at all.
+    pub fn rejects(&self, video: &Video) -> bool {
+        self.downvoted_videos.contains(&video.uuid)
+            || self.not_interested_creators.contains(&video.user_id)
+    }
+}
+
+/// The selected columns are aliased to the field names, so the rows of every
+/// dialect and schema mapping can be decoded into videos the same way.
+#[derive(Clone, Debug, Deserialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    pub uuid: VideoId,
+    pub user_id: UserId,
+    pub upvotes: i32,
+    pub downvotes: i32,
+    pub views: i32,
+    pub comments: i32,
+    pub viewtime_seconds: i64,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(try_from = "JsonHashtags")]
+    pub hashtags: Vec<String>,
+
+    //Not saved in the database, a variable to set later
+    #[serde(skip)]
+    #[sqlx(skip)]
+    pub score: f64,
+}
+
+/// The hashtags of a video as stored, a JSON array that can be null.
+#[derive(sqlx::Type)]
+#[sqlx(transparent)]
+struct JsonHashtags(Option<Json<Vec<String>>>);
+
+impl From<JsonHashtags> for Vec<String> {
+    fn from(hashtags: JsonHashtags) -> Self {
+        hashtags.0.map(|hashtags| hashtags.0).unwrap_or_default()
+    }
+}
+
+/// Connects to the database of the connection url, the dialect is chosen by its
+/// scheme (`mysql://`, `postgres://` or `sqlite:`). Every dialect has its own
+/// cargo feature, so only the compiled in dialects are supported. Without an acquire
+/// timeout the default one of the pool is used.
+pub async fn connect(
+    url: &str,
+    config: &Config,
+    acquire_timeout: Option<Duration>,
+) -> Result<Arc<dyn Store>, Box<dyn Error>> {
+    match url.split(':').next() {
+        #[cfg(feature = "mysql")]
+        Some("mysql") => Ok(Arc::new(
+            mysql::MySqlStore::connect(url, config, acquire_timeout).await?,
+        )),
+        #[cfg(feature = "postgres")]
+        Some("postgres" | "postgresql") => Ok(Arc::new(
+            postgres::PostgresStore::connect(url, config, acquire_timeout).await?,
+        )),
+        #[cfg(feature = "sqlite")]
+        Some("sqlite") => Ok(Arc::new(
+            sqlite::SqliteStore::connect(url, config, acquire_timeout).await?,
+        )),
+        scheme => Err(format!(
+            "Unsupported database {scheme:?}, it might need to be enabled with its cargo feature"
+        )
+        .into()),
+    }
+}
+
+/// Checks that every table and column of the schema exists, so a wrong mapping
+/// fails on startup and not with the first request that reads it.
+async fn validate_schema<DB: Database>(
+    schema: &SchemaConfig,
+    pool: &Pool<DB>,
+) -> Result<(), sqlx::Error>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+{
+    for (table, columns) in schema.tables() {
+        if let Err(e) = sqlx::query(&format!("SELECT * FROM {table} LIMIT 0"))
+            .execute(pool)
+            .await
+        {
+            return Err(sqlx::Error::Configuration(
+                format!("Schema table {table} can not be read: {e}").into(),
+            ));
+        }
+
+        for column in columns {
+            if let Err(e) = sqlx::query(&format!("SELECT {column} FROM {table} LIMIT 0"))
+                .execute(pool)
+                .await
+            {
+                return Err(sqlx::Error::Configuration(
+                    format!("Schema column {column} of table {table} can not be read: {e}").into(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Combines the views and likes per video within the short and the long window.
+fn merge_window_counts(
+    views: Vec<(VideoId, i64, i64)>,
+    likes: Vec<(VideoId, i64, i64)>,
+) -> HashMap<VideoId, WindowCounts> {
+    let mut counts: HashMap<VideoId, WindowCounts> = HashMap::new();
+    for (uuid, short_views, long_views) in views {
+        let video_counts = counts.entry(uuid).or_default();
+        video_counts.short_views = short_views;
+        video_counts.long_views = long_views;
+    }
+
+    for (uuid, short_likes, long_likes) in likes {
+        let video_counts = counts.entry(uuid).or_default();
+        video_counts.short_likes = short_likes;
+        video_counts.long_likes = long_likes;
+    }
+
+    counts
+}
+
+/// Decodes the rows of a bulk fetch. A row that can not be decoded (e.g. with an id
+/// that is no uuid) is logged and skipped, so one bad row does not fail the whole fetch.
+fn decode_rows<R: Row, T>(rows: Vec<R>) -> Vec<T>
+where
+    for<'r> T: FromRow<'r, R>,
+{
+    rows.iter()
+        .filter_map(|row| match T::from_row(row) {
+            Ok(decoded) => Some(decoded),
+            Err(why) => {
+                warn!("Skipping a row that can not be decoded: {why}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// The max. amount of ids that are bound to one query, the databases limit the parameters.
+const MAX_BOUND_IDS: usize = 500;
+
+/// The SQL that differs between the databases. The queries of `SqlStore` are shared,
+/// the dialects only provide the fragments they disagree on.
+pub trait Dialect: Send + Sync + 'static {
+    type Database: Database;
+
+    /// The name of the database system, for the logs.
+    const NAME: &'static str;
+
+    /// Selects the name (or version) of the database, it is logged after connecting.
+    const DESCRIBE_QUERY: &'static str;
+
+    /// An id (uuid) parameter, ids are bound as text.
+    const ID_PARAM: &'static str;
+
+    /// Selects the id (uuid) column as text, so ids are decoded the same in every dialect.
+    fn id_column(column: &str) -> String;
+
+    /// The condition that the status column is the ready status parameter (bound as text).
+    fn ready_status_condition(column: &str) -> String;
+
+    /// The condition that the timestamp column is within the last seconds (an integer parameter).
+    fn within_seconds_condition(column: &str) -> String;
+
+    /// The queries are written with `?` placeholders, dialects with
+    /// other placeholders (e.g. `$1`) replace them.
+    fn placeholders(sql: String) -> String {
+        sql
+    }
+}
+
+/// Reads everything from a SQL database, the queries are the same for
+/// every dialect except for the fragments of the `Dialect`.
+pub struct SqlStore<D: Dialect> {
+    pool: Pool<D::Database>,
+    schema: SchemaConfig,
+    dialect: PhantomData<D>,
+}
+
+// The bounds below only state what every supported database provides: executing
+// queries on the pool, binding text and integers and decoding the selected rows.
+
+impl<D: Dialect> SqlStore<D>
+where
+    for<'c> &'c mut <D::Database as Database>::Connection: Executor<'c, Database = D::Database>,
+    for<'q> <D::Database as Database>::Arguments<'q>: IntoArguments<'q, D::Database>,
+    for<'q> &'q str: Encode<'q, D::Database> + Type<D::Database>,
+    for<'q> i64: Encode<'q, D::Database> + Decode<'q, D::Database> + Type<D::Database>,
+    for<'r> String: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Json<Vec<String>>: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Video: FromRow<'r, <D::Database as Database>::Row>,
+    usize: ColumnIndex<<D::Database as Database>::Row>,
+{
+    pub async fn connect(
+        url: &str,
+        config: &Config,
+        acquire_timeout: Option<Duration>,
+    ) -> Result<Self, sqlx::Error> {
+        let mut options = PoolOptions::new().max_connections(config.max_dbpool_connections);
+        if let Some(timeout) = acquire_timeout {
+            options = options.acquire_timeout(timeout);
+        }
+        let pool = options.connect(url).await?;
+
+        let (name,): (String,) = query_as(D::DESCRIBE_QUERY).fetch_one(&pool).await?;
+        info!("Established connection to {} database: {name}", D::NAME);
+        Self::new(pool, config).await
+    }
+
+    /// Uses the connected pool (e.g. an in-memory database in the
+    /// tests), the schema of the config is validated first.
+    pub async fn new(pool: Pool<D::Database>, config: &Config) -> Result<Self, sqlx::Error> {
+        validate_schema(&config.schema, &pool).await?;
+        Ok(Self {
+            pool,
+            schema: config.schema.clone(),
+            dialect: PhantomData,
+        })
+    }
+
+    /// The selected columns of a video, aliased to the field names of `Video`.
+    fn video_columns(&self) -> String {
+        let VideoTableConfig {
+            uuid_column,
+            user_id_column,
+            comments_column,
+            up_votes_column,
+            down_votes_column,
+            views_column,
+            viewtime_column,
+            created_at_column,
+            ..
+        } = &self.schema.videos;
+        format!(
+            "{} AS uuid,
+             {} AS user_id,
+             {comments_column} AS comments,
+             {up_votes_column} AS upvotes,
+             {down_votes_column} AS downvotes,
+             {views_column} AS views,
+             {viewtime_column} AS viewtime_seconds,
+             {created_at_column} AS created_at,
+             {}",
+            D::id_column(uuid_column),
+            D::id_column(user_id_column),
+            self.hashtags_select()
+        )
+    }
+
+    /// The hashtags of a video as JSON array (named `hashtags`), from the JSON column of the
+    /// video table. With a normalized hashtags table they are set afterwards, see
+    /// `with_normalized_hashtags`, so the column is null here.
+    fn hashtags_select(&self) -> String {
+        match &self.schema.video_hashtags {
+            Some(_) => "NULL AS hashtags".to_string(),
+            None => format!("{} AS hashtags", self.schema.videos.hashtags_column),
+        }
+    }
+
+    /// Sets the hashtags of the videos from the normalized hashtags table, if there is one.
+    /// They are loaded in bulk by the video ids (the primary key of the table), so the
+    /// hashtags are not aggregated by a subquery for every selected video.
+    async fn with_normalized_hashtags(
+        &self,
+        mut videos: Vec<Video>,
+    ) -> Result<Vec<Video>, sqlx::Error> {
+        let Some(VideoHashtagsTableConfig {
+            table,
+            video_id_column,
+            hashtag_column,
+        }) = &self.schema.video_hashtags
+        else {
+            return Ok(videos);
+        };
+
+        let mut hashtags: HashMap<VideoId, Vec<String>> = HashMap::new();
+        for chunk in videos.chunks(MAX_BOUND_IDS) {
+            let placeholders = vec![D::ID_PARAM; chunk.len()].join(", ");
+            let sql = D::placeholders(format!(
+                "SELECT {}, {hashtag_column}
+                 FROM {table}
+                 WHERE {video_id_column} IN ({placeholders})",
+                D::id_column(video_id_column)
+            ));
+
+            let mut hashtags_query = query_as(&sql);
+            for video in chunk {
+                hashtags_query = hashtags_query.bind(video.uuid.as_str());
+            }
+
+            let rows: Vec<(VideoId, String)> = hashtags_query.fetch_all(&self.pool).await?;
+            for (uuid, hashtag) in rows {
+                hashtags.entry(uuid).or_default().push(hashtag);
+            }
+        }
+
+        for video in &mut videos {
+            video.hashtags = hashtags.remove(&video.uuid).unwrap_or_default();
+        }
+
+        Ok(videos)
+    }
+
+    fn ready_status_condition(&self) -> String {
+        D::ready_status_condition(&self.schema.videos.status_column)
+    }
+
+    /// The values (ids or hashtags) of the selected column of all rows of the user in the table.
+    async fn fetch_user_column<T>(
+        &self,
+        table: &str,
+        user_id_column: &str,
+        column: String,
+        uuid: &UserId,
+    ) -> Result<Vec<T>, sqlx::Error>
+    where
+        T: for<'r> Decode<'r, D::Database> + Type<D::Database> + Send + Unpin,
+    {
+        query_scalar(&D::placeholders(format!(
+            "SELECT {column}
+             FROM {table}
+             WHERE {user_id_column} = {}",
+            D::ID_PARAM
+        )))
+        .bind(uuid.as_str())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn fetch_last_viewed_videos(
+        &self,
+        uuid: &UserId,
+        amount: u32,
+    ) -> Result<Vec<VideoId>, sqlx::Error> {
+        let ViewedVideosTableConfig {
+            table,
+            user_id_column,
+            video_id_column,
+            viewed_at_column,
+        } = &self.schema.viewed_videos;
+        query_scalar(&D::placeholders(format!(
+            "SELECT {}
+             FROM {table}
+             WHERE {user_id_column} = {}
+             ORDER BY {viewed_at_column} DESC
+             LIMIT ?",
+            D::id_column(video_id_column),
+            D::ID_PARAM
+        )))
+        .bind(uuid.as_str())
+        .bind(amount as i64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// The hashtags of the last liked videos, every row holds the hashtags of one like.
+    async fn fetch_liked_hashtags(
+        &self,
+        uuid: &UserId,
+        amount: u32,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let LikedHashtagsTableConfig {
+            table,
+            user_id_column,
+            hashtags_column,
+            timestamp_column,
+        } = &self.schema.liked_hashtags;
+        let hashtags: Vec<JsonHashtags> = query_scalar(&D::placeholders(format!(
+            "SELECT {hashtags_column}
+             FROM {table}
+             WHERE {user_id_column} = {}
+             ORDER BY {timestamp_column} DESC
+             LIMIT ?",
+            D::ID_PARAM
+        )))
+        .bind(uuid.as_str())
+        .bind(amount as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(hashtags.into_iter().flat_map(Vec::from).collect())
+    }
+
+    async fn query_user(&self, uuid: &UserId, config: &Config) -> Result<User, sqlx::Error> {
+        let start_time = Instant::now();
+        let schema = &self.schema;
+
+        // May return to non-parallel if concurrency too high
+        let (
+            liked_videos,
+            following,
+            last_hashtags,
+            last_viewed,
+            downvoted_videos,
+            not_interested_hashtags,
+            not_interested_creators,
+        ) = tokio::try_join!(
+            self.fetch_user_column(
+                &schema.liked_videos.table,
+                &schema.liked_videos.user_id_column,
+                D::id_column(&schema.liked_videos.video_id_column),
+                uuid
+            ),
+            self.fetch_user_column(
+                &schema.followed_users.table,
+                &schema.followed_users.user_id_column,
+                D::id_column(&schema.followed_users.followed_users_column),
+                uuid
+            ),
+            self.fetch_liked_hashtags(uuid, config.selecting.user_hashtag_fetch_amount),
+            self.fetch_last_viewed_videos(
+                uuid,
+                config.selecting.already_viewed_videos_fetch_amount
+            ),
+            self.fetch_user_column(
+                &schema.downvoted_videos.table,
+                &schema.downvoted_videos.user_id_column,
+                D::id_column(&schema.downvoted_videos.video_id_column),
+                uuid
+            ),
+            self.fetch_user_column(
+                &schema.not_interested_hashtags.table,
+                &schema.not_interested_hashtags.user_id_column,
+                schema.not_interested_hashtags.hashtag_column.clone(),
+                uuid
+            ),
+            self.fetch_user_column(
+                &schema.not_interested_users.table,
+                &schema.not_interested_users.user_id_column,
+                D::id_column(&schema.not_interested_users.not_interested_users_column),
+                uuid
+            )
+        )?;
+
+        debug!(
+            "Fetching user took: {} ms",
+            start_time.elapsed().as_millis()
+        );
+
+        Ok(User {
+            liked_videos,
+            following,
+            last_hashtags,
+            last_viewed,
+            downvoted_videos,
+            not_interested_hashtags,
+            not_interested_creators,
+        })
+    }
+
+    async fn query_video(&self, uuid: &VideoId) -> Result<Video, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            uuid_column,
+            ..
+        } = &self.schema.videos;
+        let video = query_as(&D::placeholders(format!(
+            "SELECT {}
+             FROM {video_table}
+             WHERE {uuid_column} = {}
+               AND {}",
+            self.video_columns(),
+            D::ID_PARAM,
+            self.ready_status_condition()
+        )))
+        .bind(uuid.as_str())
+        .bind(self.schema.ready_status.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        let mut videos = self.with_normalized_hashtags(vec![video]).await?;
+        Ok(videos.remove(0))
+    }
+
+    /// Fetches the ready videos with the given ids, unknown ids are skipped. The ids are
+    /// queried in chunks, so a large batch does not exceed the parameter limit of the database.
+    async fn query_videos_by_ids(&self, ids: &[VideoId]) -> Result<Vec<Video>, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            uuid_column,
+            ..
+        } = &self.schema.videos;
+        let mut videos = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_BOUND_IDS) {
+            let placeholders = vec![D::ID_PARAM; chunk.len()].join(", ");
+            let sql = D::placeholders(format!(
+                "SELECT {}
+                 FROM {video_table}
+                 WHERE {}
+                   AND {uuid_column} IN ({placeholders})",
+                self.video_columns(),
+                self.ready_status_condition()
+            ));
+
+            let mut ids_query = query(&sql).bind(self.schema.ready_status.as_str());
+            for id in chunk {
+                ids_query = ids_query.bind(id.as_str());
+            }
+
+            let rows = ids_query.fetch_all(&self.pool).await?;
+            videos.extend(decode_rows(rows));
+        }
+
+        self.with_normalized_hashtags(videos).await
+    }
+
+    /// Fetches the newest ready videos (up to the amount), for the candidate pool.
+    async fn query_ready_videos(&self, amount: u32) -> Result<Vec<Video>, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            created_at_column,
+            ..
+        } = &self.schema.videos;
+        let rows = query(&D::placeholders(format!(
+            "SELECT {}
+             FROM {video_table}
+             WHERE {}
+             ORDER BY {created_at_column} DESC
+             LIMIT ?",
+            self.video_columns(),
+            self.ready_status_condition()
+        )))
+        .bind(self.schema.ready_status.as_str())
+        .bind(amount as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.with_normalized_hashtags(decode_rows(rows)).await
+    }
+
+    /// Videos that do not have enough views for reliable stats yet, newest first.
+    async fn query_fresh_videos(&self, config: &Config) -> Result<Vec<Video>, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            views_column,
+            created_at_column,
+            ..
+        } = &self.schema.videos;
+        let rows = query(&D::placeholders(format!(
+            "SELECT {}
+             FROM {video_table}
+             WHERE {}
+               AND {views_column} < ?
+             ORDER BY {created_at_column} DESC
+             LIMIT ?",
+            self.video_columns(),
+            self.ready_status_condition()
+        )))
+        .bind(self.schema.ready_status.as_str())
+        .bind(config.selecting.exploration_max_views as i64)
+        .bind(config.selecting.exploration_fetch_amount as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.with_normalized_hashtags(decode_rows(rows)).await
+    }
+
+    async fn fetch_popular_videos(&self, config: &Config) -> Result<Vec<Video>, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            up_votes_column,
+            ..
+        } = &self.schema.videos;
+        let rows = query(&D::placeholders(format!(
+            "SELECT {}
+             FROM {video_table}
+             WHERE {}
+             ORDER BY {up_votes_column} DESC
+             LIMIT ?",
+            self.video_columns(),
+            self.ready_status_condition()
+        )))
+        .bind(self.schema.ready_status.as_str())
+        .bind(config.cold_start.popular_fetch_amount as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.with_normalized_hashtags(decode_rows(rows)).await
+    }
+
+    /// The most viewed videos that were uploaded recently.
+    async fn fetch_trending_videos(&self, config: &Config) -> Result<Vec<Video>, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            views_column,
+            created_at_column,
+            ..
+        } = &self.schema.videos;
+        let rows = query(&D::placeholders(format!(
+            "SELECT {}
+             FROM {video_table}
+             WHERE {}
+               AND {}
+             ORDER BY {views_column} DESC
+             LIMIT ?",
+            self.video_columns(),
+            self.ready_status_condition(),
+            D::within_seconds_condition(created_at_column)
+        )))
+        .bind(self.schema.ready_status.as_str())
+        .bind(config.cold_start.trending_max_age_hours as i64 * 3600)
+        .bind(config.cold_start.trending_fetch_amount as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.with_normalized_hashtags(decode_rows(rows)).await
+    }
+
+    /// Returns popular and trending videos for the cold start.
+    async fn query_cold_start_videos(
+        &self,
+        config: &Config,
+    ) -> Result<(Vec<Video>, Vec<Video>), sqlx::Error> {
+        let start_time = Instant::now();
+
+        let videos = tokio::try_join!(
+            self.fetch_popular_videos(config),
+            self.fetch_trending_videos(config)
+        )?;
+
+        debug!(
+            "Fetching cold start videos took: {} ms",
+            start_time.elapsed().as_millis()
+        );
+
+        Ok(videos)
+    }
+
+    /// Fetches (user id, video id) pairs of all likes, for the co-like index.
+    async fn query_likes(&self, amount: u32) -> Result<Vec<(UserId, VideoId)>, sqlx::Error> {
+        let LikedVideosTableConfig {
+            table,
+            user_id_column,
+            video_id_column,
+            ..
+        } = &self.schema.liked_videos;
+        let rows = query(&D::placeholders(format!(
+            "SELECT {}, {}
+             FROM {table}
+             LIMIT ?",
+            D::id_column(user_id_column),
+            D::id_column(video_id_column)
+        )))
+        .bind(amount as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(decode_rows(rows))
+    }
+
+    /// Fetches the hashtags of the ready videos, for the hashtag graph.
+    async fn query_videos_hashtags(&self, amount: u32) -> Result<Vec<Vec<String>>, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            uuid_column,
+            hashtags_column,
+            ..
+        } = &self.schema.videos;
+        let Some(VideoHashtagsTableConfig {
+            table,
+            video_id_column,
+            hashtag_column,
+        }) = &self.schema.video_hashtags
+        else {
+            let videos_hashtags: Vec<JsonHashtags> = query_scalar(&D::placeholders(format!(
+                "SELECT {hashtags_column}
+                 FROM {video_table}
+                 WHERE {}
+                 LIMIT ?",
+                self.ready_status_condition()
+            )))
+            .bind(self.schema.ready_status.as_str())
+            .bind(amount as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+            return Ok(videos_hashtags.into_iter().map(Vec::from).collect());
+        };
+
+        // The rows of the normalized table, joined with the ready videos (up to the amount)
+        let rows: Vec<(VideoId, String)> = query_as(&D::placeholders(format!(
+            "SELECT {}, {table}.{hashtag_column}
+             FROM {table}
+             JOIN (SELECT {uuid_column} AS uuid
+                   FROM {video_table}
+                   WHERE {}
+                   LIMIT ?) AS ready_videos
+               ON ready_videos.uuid = {table}.{video_id_column}",
+            D::id_column(&format!("{table}.{video_id_column}")),
+            self.ready_status_condition()
+        )))
+        .bind(self.schema.ready_status.as_str())
+        .bind(amount as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut videos_hashtags: HashMap<VideoId, Vec<String>> = HashMap::new();
+        for (uuid, hashtag) in rows {
+            videos_hashtags.entry(uuid).or_default().push(hashtag);
+        }
+
+        Ok(videos_hashtags.into_values().collect())
+    }
+
+    /// Counts the events (views or likes) per video within the short and the long window.
+    /// Only events of ready videos count, so unready or deleted videos are never trending.
+    async fn fetch_window_counts(
+        &self,
+        table: &str,
+        video_id_column: &str,
+        timestamp_column: &str,
+        config: &Config,
+    ) -> Result<Vec<(VideoId, i64, i64)>, sqlx::Error> {
+        let VideoTableConfig {
+            table: video_table,
+            uuid_column,
+            status_column,
+            ..
+        } = &self.schema.videos;
+        let timestamp_column = format!("{table}.{timestamp_column}");
+        query_as(&D::placeholders(format!(
+            "SELECT {},
+                    COUNT(CASE WHEN {} THEN 1 END),
+                    COUNT(*)
+             FROM {table}
+             JOIN {video_table} ON {video_table}.{uuid_column} = {table}.{video_id_column}
+             WHERE {}
+               AND {}
+             GROUP BY {table}.{video_id_column}",
+            D::id_column(&format!("{table}.{video_id_column}")),
+            D::within_seconds_condition(&timestamp_column),
+            D::within_seconds_condition(&timestamp_column),
+            D::ready_status_condition(&format!("{video_table}.{status_column}"))
+        )))
+        .bind(config.trending.short_window_seconds as i64)
+        .bind(config.trending.long_window_seconds as i64)
+        .bind(self.schema.ready_status.as_str())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// The views and likes per video within the trending windows.
+    async fn query_trending_counts(
+        &self,
+        config: &Config,
+    ) -> Result<HashMap<VideoId, WindowCounts>, sqlx::Error> {
+        let ViewedVideosTableConfig {
+            table: viewed_videos_table,
+            video_id_column: viewed_video_id_column,
+            viewed_at_column,
+            ..
+        } = &self.schema.viewed_videos;
+        let LikedVideosTableConfig {
+            table: liked_videos_table,
+            video_id_column: liked_video_id_column,
+            liked_at_column,
+            ..
+        } = &self.schema.liked_videos;
+        let (views, likes) = tokio::try_join!(
+            self.fetch_window_counts(
+                viewed_videos_table,
+                viewed_video_id_column,
+                viewed_at_column,
+                config
+            ),
+            self.fetch_window_counts(
+                liked_videos_table,
+                liked_video_id_column,
+                liked_at_column,
+                config
+            )
+        )?;
+
+        Ok(merge_window_counts(views, likes))
+    }
+}
+
+impl<D: Dialect> VideoStore for SqlStore<D>
+where
+    for<'c> &'c mut <D::Database as Database>::Connection: Executor<'c, Database = D::Database>,
+    for<'q> <D::Database as Database>::Arguments<'q>: IntoArguments<'q, D::Database>,
+    for<'q> &'q str: Encode<'q, D::Database> + Type<D::Database>,
+    for<'q> i64: Encode<'q, D::Database> + Decode<'q, D::Database> + Type<D::Database>,
+    for<'r> String: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Json<Vec<String>>: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Video: FromRow<'r, <D::Database as Database>::Row>,
+    usize: ColumnIndex<<D::Database as Database>::Row>,
+{
+    fn fetch_video<'a>(&'a self, uuid: &'a VideoId, _config: &'a Config) -> StoreFuture<'a, Video> {
+        store::boxed(self.query_video(uuid))
+    }
+
+    fn fetch_videos_by_ids<'a>(&'a self, ids: &'a [VideoId]) -> StoreFuture<'a, Vec<Video>> {
+        store::boxed(self.query_videos_by_ids(ids))
+    }
+
+    fn fetch_ready_videos(&self, amount: u32) -> StoreFuture<'_, Vec<Video>> {
+        store::boxed(self.query_ready_videos(amount))
+    }
+
+    fn fetch_fresh_videos<'a>(&'a self, config: &'a Config) -> StoreFuture<'a, Vec<Video>> {
+        store::boxed(self.query_fresh_videos(config))
+    }
+
+    fn fetch_cold_start_videos<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> StoreFuture<'a, (Vec<Video>, Vec<Video>)> {
+        store::boxed(self.query_cold_start_videos(config))
+    }
+
+    fn fetch_likes(&self, amount: u32) -> StoreFuture<'_, Vec<(UserId, VideoId)>> {
+        store::boxed(self.query_likes(amount))
+    }
+
+    fn fetch_videos_hashtags(&self, amount: u32) -> StoreFuture<'_, Vec<Vec<String>>> {
+        store::boxed(self.query_videos_hashtags(amount))
+    }
+
+    fn fetch_trending_counts<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> StoreFuture<'a, HashMap<VideoId, WindowCounts>> {
+        store::boxed(self.query_trending_counts(config))
+    }
+}
+
+impl<D: Dialect> UserStore for SqlStore<D>
+where
+    for<'c> &'c mut <D::Database as Database>::Connection: Executor<'c, Database = D::Database>,
+    for<'q> <D::Database as Database>::Arguments<'q>: IntoArguments<'q, D::Database>,
+    for<'q> &'q str: Encode<'q, D::Database> + Type<D::Database>,
+    for<'q> i64: Encode<'q, D::Database> + Decode<'q, D::Database> + Type<D::Database>,
+    for<'r> String: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Json<Vec<String>>: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Video: FromRow<'r, <D::Database as Database>::Row>,
+    usize: ColumnIndex<<D::Database as Database>::Row>,
+{
+    fn fetch_user<'a>(&'a self, uuid: &'a UserId, config: &'a Config) -> StoreFuture<'a, User> {
+        store::boxed(self.query_user(uuid, config))
+    }
+}
+
+impl<D: Dialect> Store for SqlStore<D>
+where
+    for<'c> &'c mut <D::Database as Database>::Connection: Executor<'c, Database = D::Database>,
+    for<'q> <D::Database as Database>::Arguments<'q>: IntoArguments<'q, D::Database>,
+    for<'q> &'q str: Encode<'q, D::Database> + Type<D::Database>,
+    for<'q> i64: Encode<'q, D::Database> + Decode<'q, D::Database> + Type<D::Database>,
+    for<'r> String: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Json<Vec<String>>: Decode<'r, D::Database> + Type<D::Database>,
+    for<'r> Video: FromRow<'r, <D::Database as Database>::Row>,
+    usize: ColumnIndex<<D::Database as Database>::Row>,
+{
+    fn ping(&self) -> StoreFuture<'_, ()> {
+        store::boxed(async {
+            sqlx::query("SELECT 1").execute(&self.pool).await?;
+            Ok(())
+        })
+    }
+}
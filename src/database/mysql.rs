@@ -0,0 +1,29 @@
+use sqlx::MySql;
+
+use super::{Dialect, SqlStore};
+
+/// Reads everything from a MySQL database. Ids are stored as `BINARY(16)`
+/// and the hashtags of a video as a JSON array.
+pub type MySqlStore = SqlStore<MySqlDialect>;
+
+pub struct MySqlDialect;
+
+impl Dialect for MySqlDialect {
+    type Database = MySql;
+
+    const NAME: &'static str = "MySQL";
+    const DESCRIBE_QUERY: &'static str = "SELECT DATABASE()";
+    const ID_PARAM: &'static str = "UUID_TO_BIN(?)";
+
+    fn id_column(column: &str) -> String {
+        format!("BIN_TO_UUID({column})")
+    }
+
+    fn ready_status_condition(column: &str) -> String {
+        format!("{column} = ?")
+    }
+
+    fn within_seconds_condition(column: &str) -> String {
+        format!("{column} >= NOW() - INTERVAL ? SECOND")
+    }
+}
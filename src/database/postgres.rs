@@ -0,0 +1,54 @@
+use sqlx::Postgres;
+
+use super::{Dialect, SqlStore};
+
+/// Reads everything from a PostgreSQL database. Ids are stored as `UUID`,
+/// timestamps as `TIMESTAMPTZ` and the hashtags of a video as a `JSONB` array.
+pub type PostgresStore = SqlStore<PostgresDialect>;
+
+pub struct PostgresDialect;
+
+impl Dialect for PostgresDialect {
+    type Database = Postgres;
+
+    const NAME: &'static str = "PostgreSQL";
+    const DESCRIBE_QUERY: &'static str = "SELECT current_database()";
+    const ID_PARAM: &'static str = "CAST(? AS UUID)";
+
+    fn id_column(column: &str) -> String {
+        format!("CAST({column} AS TEXT)")
+    }
+
+    /// The status is compared as text, so it does not matter if it is stored as a number.
+    fn ready_status_condition(column: &str) -> String {
+        format!("CAST({column} AS TEXT) = ?")
+    }
+
+    fn within_seconds_condition(column: &str) -> String {
+        format!("{column} >= NOW() - make_interval(secs => CAST(? AS DOUBLE PRECISION))")
+    }
+
+    /// Numbers the placeholders (`$1`, `$2`, ...), the queries contain no other `?`.
+    fn placeholders(sql: String) -> String {
+        let mut numbered = String::with_capacity(sql.len());
+        for (i, part) in sql.split('?').enumerate() {
+            if i > 0 {
+                numbered.push_str(&format!("${i}"));
+            }
+            numbered.push_str(part);
+        }
+
+        numbered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_the_placeholders() {
+        let sql = PostgresDialect::placeholders("a = CAST(? AS UUID) AND b IN (?, ?)".to_string());
+        assert_eq!(sql, "a = CAST($1 AS UUID) AND b IN ($2, $3)");
+    }
+}
@@ -0,0 +1,252 @@
+use sqlx::Sqlite;
+
+use super::{Dialect, SqlStore};
+
+/// Reads everything from a SQLite database, e.g. a local file for development.
+/// Ids are stored as (hyphenated, lowercase) text, timestamps as
+/// `YYYY-MM-DD HH:MM:SS` or RFC 3339 text and the hashtags of a video as a JSON array.
+pub type SqliteStore = SqlStore<SqliteDialect>;
+
+pub struct SqliteDialect;
+
+impl Dialect for SqliteDialect {
+    type Database = Sqlite;
+
+    const NAME: &'static str = "SQLite";
+    const DESCRIBE_QUERY: &'static str = "SELECT 'version ' || sqlite_version()";
+    const ID_PARAM: &'static str = "?";
+
+    fn id_column(column: &str) -> String {
+        column.to_string()
+    }
+
+    fn ready_status_condition(column: &str) -> String {
+        format!("{column} = ?")
+    }
+
+    /// Timestamps are text, so they are compared as datetimes.
+    fn within_seconds_condition(column: &str) -> String {
+        format!("datetime({column}) >= datetime('now', '-' || ? || ' seconds')")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{raw_sql, sqlite::SqlitePoolOptions};
+
+    use super::*;
+    use crate::{
+        config::Config,
+        database::{UserId, VideoId, MAX_BOUND_IDS},
+        store::{UserStore, VideoStore},
+    };
+
+    const USER: &str = "00000000-0000-0000-0000-000000000001";
+    const CREATOR: &str = "00000000-0000-0000-0000-000000000002";
+    const OLD_VIDEO: &str = "00000000-0000-0000-0000-00000000000a";
+    const NEW_VIDEO: &str = "00000000-0000-0000-0000-00000000000b";
+    const UNREADY_VIDEO: &str = "00000000-0000-0000-0000-00000000000c";
+
+    const ROWS: &str = "
+        INSERT INTO video (uuid, user_id, up_votes, views, created_at, hashtags, status) VALUES
+            ('00000000-0000-0000-0000-00000000000a', '00000000-0000-0000-0000-000000000002',
+             50, 400, datetime('now', '-3 days'), '[\"cats\", \"music\"]', 3),
+            ('00000000-0000-0000-0000-00000000000b', '00000000-0000-0000-0000-000000000002',
+             5, 20, datetime('now', '-1 hours'), '[\"dogs\"]', 3),
+            ('00000000-0000-0000-0000-00000000000c', '00000000-0000-0000-0000-000000000002',
+             0, 0, datetime('now'), NULL, 1);
+        INSERT INTO liked_videos (user_id, video_id) VALUES
+            ('00000000-0000-0000-0000-000000000001', '00000000-0000-0000-0000-00000000000a'),
+            ('00000000-0000-0000-0000-000000000001', '00000000-0000-0000-0000-00000000000c');
+        INSERT INTO viewed_video (user_id, video_id, viewed_at) VALUES
+            ('00000000-0000-0000-0000-000000000001', '00000000-0000-0000-0000-00000000000a',
+             datetime('now', '-2 days')),
+            ('00000000-0000-0000-0000-000000000001', '00000000-0000-0000-0000-00000000000b',
+             datetime('now', '-1 minutes'));
+        INSERT INTO user_followed_users (user_id, followed_users) VALUES
+            ('00000000-0000-0000-0000-000000000001', '00000000-0000-0000-0000-000000000002');
+        INSERT INTO user_liked_hastags (user_id, hashtags) VALUES
+            ('00000000-0000-0000-0000-000000000001', '[\"cats\", \"music\"]');
+    ";
+
+    fn video_id(id: &str) -> VideoId {
+        VideoId::parse(id).unwrap()
+    }
+
+    fn user_id(id: &str) -> UserId {
+        UserId::parse(id).unwrap()
+    }
+
+    /// A store on an in-memory database with the development schema. The pool has
+    /// one connection, because every connection would open its own in-memory database.
+    async fn store(config: &Config) -> SqliteStore {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        raw_sql(include_str!("../../schema/sqlite.sql"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        raw_sql(ROWS).execute(&pool).await.unwrap();
+        SqliteStore::new(pool, config).await.unwrap()
+    }
+
+    fn config() -> Config {
+        serde_json::from_str(include_str!("../../config.json")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_users_and_ready_videos() {
+        let config = config();
+        let store = store(&config).await;
+
+        let user = store.fetch_user(&user_id(USER), &config).await.unwrap();
+        assert_eq!(
+            user.liked_videos,
+            vec![video_id(OLD_VIDEO), video_id(UNREADY_VIDEO)]
+        );
+        assert_eq!(
+            user.last_viewed,
+            vec![video_id(NEW_VIDEO), video_id(OLD_VIDEO)]
+        );
+        assert_eq!(user.following, vec![user_id(CREATOR)]);
+        assert_eq!(user.last_hashtags, vec!["cats", "music"]);
+
+        let unknown = store.fetch_user(&user_id(CREATOR), &config).await.unwrap();
+        assert!(unknown.liked_videos.is_empty() && unknown.last_hashtags.is_empty());
+
+        let video = store
+            .fetch_video(&video_id(OLD_VIDEO), &config)
+            .await
+            .unwrap();
+        assert_eq!((video.user_id, video.views), (user_id(CREATOR), 400));
+        assert_eq!(video.hashtags, vec!["cats", "music"]);
+        assert!(store
+            .fetch_video(&video_id(UNREADY_VIDEO), &config)
+            .await
+            .is_err());
+
+        // Newest first, without the unready video
+        let ready: Vec<VideoId> = store
+            .fetch_ready_videos(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|video| video.uuid)
+            .collect();
+        assert_eq!(ready, vec![video_id(NEW_VIDEO), video_id(OLD_VIDEO)]);
+    }
+
+    #[tokio::test]
+    async fn counts_trending_events_of_ready_videos() {
+        let config = config();
+        let store = store(&config).await;
+
+        let counts = store.fetch_trending_counts(&config).await.unwrap();
+        let new_video = &counts[&video_id(NEW_VIDEO)];
+        assert_eq!((new_video.short_views, new_video.long_views), (1, 1));
+        let old_video = &counts[&video_id(OLD_VIDEO)];
+        assert_eq!((old_video.short_views, old_video.long_views), (0, 1));
+        assert_eq!((old_video.short_likes, old_video.long_likes), (1, 1));
+        assert!(!counts.contains_key(&video_id(UNREADY_VIDEO)));
+
+        let (popular, trending) = store.fetch_cold_start_videos(&config).await.unwrap();
+        assert_eq!(popular[0].uuid, video_id(OLD_VIDEO));
+        assert!(trending
+            .iter()
+            .all(|video| video.uuid == video_id(NEW_VIDEO)));
+    }
+
+    #[tokio::test]
+    async fn fetches_batches_larger_than_the_bound_ids_in_chunks() {
+        let config = config();
+        let store = store(&config).await;
+
+        // Unknown ids, with the known videos in the first and in the last chunk
+        let mut ids: Vec<VideoId> = (100..100 + 2 * MAX_BOUND_IDS as u32)
+            .map(|i| video_id(&format!("00000000-0000-0000-0000-{i:012}")))
+            .collect();
+        ids[0] = video_id(OLD_VIDEO);
+        ids.push(video_id(NEW_VIDEO));
+
+        let mut videos: Vec<VideoId> = store
+            .fetch_videos_by_ids(&ids)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|video| video.uuid)
+            .collect();
+        videos.sort();
+        assert_eq!(videos, vec![video_id(OLD_VIDEO), video_id(NEW_VIDEO)]);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_schema_with_unknown_columns() {
+        let mut config = config();
+        config.schema.liked_videos.video_id_column = "liked_video".to_string();
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        raw_sql(include_str!("../../schema/sqlite.sql"))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(SqliteStore::new(pool, &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_the_hashtags_from_the_normalized_table() {
+        let mut config = config();
+        config.schema.video_hashtags = Some(Default::default());
+        let store = store(&config).await;
+        raw_sql(
+            "INSERT INTO video_hashtags (video_id, hashtag) VALUES
+                ('00000000-0000-0000-0000-00000000000a', 'birds'),
+                ('00000000-0000-0000-0000-00000000000c', 'fish')",
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let video = store
+            .fetch_video(&video_id(OLD_VIDEO), &config)
+            .await
+            .unwrap();
+        assert_eq!(video.hashtags, vec!["birds"]);
+
+        let ready = store.fetch_ready_videos(10).await.unwrap();
+        assert_eq!(ready[0].uuid, video_id(NEW_VIDEO));
+        assert!(ready[0].hashtags.is_empty());
+
+        // Only the hashtags of ready videos
+        let videos_hashtags = store.fetch_videos_hashtags(10).await.unwrap();
+        assert_eq!(videos_hashtags, vec![vec!["birds".to_string()]]);
+    }
+
+    #[tokio::test]
+    async fn skips_rows_that_can_not_be_decoded() {
+        let config = config();
+        let store = store(&config).await;
+        raw_sql(
+            "INSERT INTO video (uuid, user_id, created_at, status) VALUES
+                ('not-a-uuid', '00000000-0000-0000-0000-000000000002', datetime('now'), 3)",
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let ready: Vec<VideoId> = store
+            .fetch_ready_videos(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|video| video.uuid)
+            .collect();
+        assert_eq!(ready, vec![video_id(NEW_VIDEO), video_id(OLD_VIDEO)]);
+    }
+}
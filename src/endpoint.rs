@@ -1,15 +1,22 @@
-use std::{sync::{Arc, Mutex}, time::Instant};
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::Instant};
 
 use axum::{http::StatusCode, Extension, Json};
 use log::{debug, error, info, warn};
+use rand::{rngs::StdRng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use sqlx::MySqlPool;
 
 use crate::{
     algorithm,
     config::{self, Config},
-    database::{DatabaseModel, User, Video},
+    database::{UserId, VideoId},
+    experiment,
+    indexes::Indexes,
+    scoring::ScoreExplanation,
+    session::FeedSessions,
+    store::{Store, StoreError},
+    trending::TrendingVideo,
+    user_cache::{UserCache, UserCacheStats},
 };
 
 #[derive(Deserialize, Serialize)]
@@ -19,28 +26,85 @@ pub struct ScoreVideoResponse {
 
 #[derive(Deserialize, Serialize)]
 pub struct ScoreVideoRequest {
-    uuid: String,
+    uuid: VideoId,
 }
 
 //#[debug_handler]
 pub async fn score_video(
-    Extension(db_pool): Extension<Arc<MySqlPool>>,
+    Extension(store): Extension<Arc<dyn Store>>,
     Extension(config): Extension<Arc<Mutex<Config>>>,
+    Extension(indexes): Extension<Arc<Indexes>>,
     Json(payload): Json<ScoreVideoRequest>,
 ) -> Result<Json<ScoreVideoResponse>, StatusCode> {
     let config = config.lock().unwrap().clone();
-    match Video::from_db(&payload.uuid, &db_pool, &config).await {
+
+    // Deleted videos are removed from the score index (see /removeVideo),
+    // so only videos without a precomputed score are fetched
+    if let Some(score) = indexes.scores.read().unwrap().get(&payload.uuid, &config) {
+        return Ok(Json(ScoreVideoResponse { score }));
+    }
+
+    match store.fetch_video(&payload.uuid, &config).await {
         Ok(video) => {
             let score = algorithm::score_video(&video, &config);
             Ok(Json(ScoreVideoResponse { score }))
         }
-        Err(why) => {
-            warn!("Error retrieving video data (maybe video id 404): {} ", why);
-            Err(StatusCode::NOT_FOUND)
-        }
+        Err(why) => Err(store_error_status("Error retrieving video data", why)),
     }
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct ScoreVideosRequest {
+    uuids: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct VideoScore {
+    uuid: String,
+    score: Option<f64>, //None if the video does not exist or is not ready
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ScoreVideosResponse {
+    videos: Vec<VideoScore>, //In the order of the request
+}
+
+/// Scores many videos at once (e.g. search results), with one query for all
+/// videos that are not in the score index.
+//#[debug_handler]
+pub async fn score_videos(
+    Extension(store): Extension<Arc<dyn Store>>,
+    Extension(config): Extension<Arc<Mutex<Config>>>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+    Json(payload): Json<ScoreVideosRequest>,
+) -> Result<Json<ScoreVideosResponse>, StatusCode> {
+    let config = config.lock().unwrap().clone();
+    check_score_batch_size(payload.uuids.len(), &config)?;
+
+    let ids = normalize_video_ids(&payload.uuids);
+    let mut scores: HashMap<VideoId, f64> = {
+        let index = indexes.scores.read().unwrap();
+        ids.iter()
+            .filter_map(|id| Some((id.clone(), index.get(id, &config)?)))
+            .collect()
+    };
+
+    let missing: Vec<VideoId> = ids
+        .into_iter()
+        .filter(|id| !scores.contains_key(id))
+        .collect();
+    let videos = store.fetch_videos_by_ids(&missing)
+        .await
+        .map_err(|why| store_error_status("Error retrieving videos data", why))?;
+    scores.extend(
+        videos
+            .iter()
+            .map(|video| (video.uuid.clone(), algorithm::score_video(video, &config))),
+    );
+
+    Ok(Json(video_scores(payload.uuids, &scores)))
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct PersonalizeScoreResponse {
     score: f64,
@@ -49,76 +113,330 @@ pub struct PersonalizeScoreResponse {
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PersonalizeVideoRequest {
-    user_id: String,
-    video_id: String,
+    user_id: UserId,
+    video_id: VideoId,
 }
 
 //#[debug_handler]
 pub async fn score_video_personalized(
-    Extension(db_pool): Extension<Arc<MySqlPool>>,
+    Extension(store): Extension<Arc<dyn Store>>,
     Extension(config): Extension<Arc<Mutex<Config>>>,
+    Extension(user_cache): Extension<Arc<UserCache>>,
     Json(payload): Json<PersonalizeVideoRequest>,
 ) -> Result<Json<PersonalizeScoreResponse>, StatusCode> {
     let config = config.lock().unwrap().clone();
-    match Video::from_db(&payload.video_id, &db_pool, &config).await {
-        Ok(video) => match User::from_db(&payload.user_id, &db_pool, &config).await {
+    let (variant, config) = experiment::assign(&config, payload.user_id.as_str());
+    match store.fetch_video(&payload.video_id, config).await {
+        Ok(video) => match user_cache.get(&payload.user_id, variant, store.as_ref(), config).await {
             Ok(user) => {
                 let score =
-                    algorithm::score_video_personalized(&user, &video, &config);
+                    algorithm::score_video_personalized(&user, &video, config);
                 Ok(Json(PersonalizeScoreResponse { score }))
             }
 
-            Err(why) => {
-                error!("Error retrieving user data: {}", why);
-                Err(StatusCode::NOT_FOUND)
-            }
+            Err(why) => Err(store_error_status("Error retrieving user data", why)),
         },
 
-        Err(why) => {
-            error!("Error retrieving video data: {}", why);
-            Err(StatusCode::NOT_FOUND)
+        Err(why) => Err(store_error_status("Error retrieving video data", why)),
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalizeVideosRequest {
+    user_id: UserId,
+    video_ids: Vec<String>,
+}
+
+/// Personalized scores of many videos at once, see `score_videos`.
+//#[debug_handler]
+pub async fn score_videos_personalized(
+    Extension(store): Extension<Arc<dyn Store>>,
+    Extension(config): Extension<Arc<Mutex<Config>>>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+    Extension(user_cache): Extension<Arc<UserCache>>,
+    Json(payload): Json<PersonalizeVideosRequest>,
+) -> Result<Json<ScoreVideosResponse>, StatusCode> {
+    let config = config.lock().unwrap().clone();
+    let (variant, config) = experiment::assign(&config, payload.user_id.as_str());
+    check_score_batch_size(payload.video_ids.len(), config)?;
+
+    let ids = normalize_video_ids(&payload.video_ids);
+    let (user, videos) = tokio::try_join!(
+        user_cache.get(&payload.user_id, variant, store.as_ref(), config),
+        store.fetch_videos_by_ids(&ids)
+    )
+    .map_err(|why| store_error_status("Error retrieving user or videos data", why))?;
+
+    let scores: HashMap<VideoId, f64> = {
+        let index = indexes.scores.read().unwrap();
+        videos
+            .iter()
+            .map(|video| {
+                let score = match index.get(&video.uuid, config) {
+                    Some(score) => algorithm::personalize_score(score, &user, video, config),
+                    None => algorithm::score_video_personalized(&user, video, config),
+                };
+                (video.uuid.clone(), score)
+            })
+            .collect()
+    };
+
+    Ok(Json(video_scores(payload.video_ids, &scores)))
+}
+
+fn check_score_batch_size(size: usize, config: &Config) -> Result<(), StatusCode> {
+    if size > config.max_score_batch_size as usize {
+        warn!("Score batch of {size} videos denied, the max. is {}", config.max_score_batch_size);
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    Ok(())
+}
+
+/// Maps a failed store call to the status of the response: a missing (or unready)
+/// video is 404, a database that can not be reached 503 and anything else (e.g. a row
+/// that can not be decoded) 500. An unknown user is an empty user and no error.
+/// Only the unexpected errors are logged as errors.
+fn store_error_status(context: &str, why: StoreError) -> StatusCode {
+    match why {
+        StoreError::NotFound => {
+            warn!("{context}: {why}");
+            StatusCode::NOT_FOUND
+        }
+        StoreError::Unavailable(_) => {
+            error!("{context}: {why}");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        StoreError::Decode(_) | StoreError::Database(_) => {
+            error!("{context}: {why}");
+            StatusCode::INTERNAL_SERVER_ERROR
         }
     }
 }
 
+/// The ids of the batch as video ids. Invalid ids are skipped, they can not exist anyway.
+fn normalize_video_ids(ids: &[String]) -> Vec<VideoId> {
+    ids.iter().filter_map(|id| VideoId::parse(id).ok()).collect()
+}
+
+/// The scores in the order of the requested ids, missing videos have no score.
+fn video_scores(ids: Vec<String>, scores: &HashMap<VideoId, f64>) -> ScoreVideosResponse {
+    let videos = ids
+        .into_iter()
+        .map(|uuid| {
+            let score = VideoId::parse(&uuid)
+                .ok()
+                .and_then(|id| scores.get(&id).copied());
+            VideoScore { uuid, score }
+        })
+        .collect();
+
+    ScoreVideosResponse { videos }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainScoreRequest {
+    video_id: VideoId,
+    user_id: Option<UserId>, //Explains the personalized score if set
+}
+
+//#[debug_handler]
+pub async fn explain_score(
+    Extension(store): Extension<Arc<dyn Store>>,
+    Extension(config): Extension<Arc<Mutex<Config>>>,
+    Json(payload): Json<ExplainScoreRequest>,
+) -> Result<Json<ScoreExplanation>, StatusCode> {
+    let config = config.lock().unwrap().clone();
+    let config = match &payload.user_id {
+        Some(user_id) => experiment::assign(&config, user_id.as_str()).1,
+        None => &config,
+    };
+    let video = store.fetch_video(&payload.video_id, config)
+        .await
+        .map_err(|why| store_error_status("Error retrieving video data", why))?;
+
+    let explanation = match payload.user_id {
+        Some(user_id) => {
+            let user = store.fetch_user(&user_id, config)
+                .await
+                .map_err(|why| store_error_status("Error retrieving user data", why))?;
+            algorithm::explain_score_personalized(&user, &video, config)
+        }
+        None => algorithm::explain_score(&video, config),
+    };
+
+    Ok(Json(explanation))
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NextVideosRequest {
-    user_id: String,
+    user_id: UserId,
+    cursor: Option<String>, //The cursor of the last response, continues the feed session
+    seed: Option<u64>,      //Only used by internal callers, to replay a feed
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct NextVideosResponse {
-    videos: Vec<String>, //Vec of UUIDs of videos
+    videos: Vec<VideoId>,   //Vec of UUIDs of videos
+    cursor: Option<String>, //Send this with the next request to not get the same videos again
+    seed: u64,              //The seed the selection used, can be sent to /replayNextVideos
+    variant: String,        //The experiment the user is in, or "control"
 }
 
 //#[debug_handler]
 pub async fn next_videos(
-    Extension(db_pool): Extension<Arc<MySqlPool>>,
+    Extension(store): Extension<Arc<dyn Store>>,
     Extension(config): Extension<Arc<Mutex<Config>>>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+    Extension(user_cache): Extension<Arc<UserCache>>,
+    Extension(sessions): Extension<Arc<FeedSessions>>,
+    Json(payload): Json<NextVideosRequest>,
+) -> Result<Json<NextVideosResponse>, StatusCode> {
+    if payload.seed.is_some() {
+        warn!("Ignoring seed of next videos request, seeds are only accepted internally");
+    }
+
+    let config = config.lock().unwrap().clone();
+    let (cursor, served) = sessions.resume(payload.cursor.as_deref(), &payload.user_id, &config);
+    let videos = select_next_videos(
+        store.as_ref(),
+        &config,
+        &indexes,
+        &user_cache,
+        &payload.user_id,
+        &served,
+        rand::random(),
+    )
+    .await?;
+
+    sessions.record(&cursor, &videos.videos);
+    Ok(Json(NextVideosResponse {
+        cursor: Some(cursor),
+        ..videos
+    }))
+}
+
+/// Same as `next_videos`, but uses the seed of the request (if set) so
+/// a feed that was reported can be reproduced exactly. Feed sessions are
+/// not used, so this replays the first page of a session.
+//#[debug_handler]
+pub async fn replay_next_videos(
+    Extension(store): Extension<Arc<dyn Store>>,
+    Extension(config): Extension<Arc<Mutex<Config>>>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+    Extension(user_cache): Extension<Arc<UserCache>>,
     Json(payload): Json<NextVideosRequest>,
 ) -> Result<Json<NextVideosResponse>, StatusCode> {
-    let start_time = Instant::now();
     let config = config.lock().unwrap().clone();
-    let user = User::from_db(&payload.user_id, &db_pool, &config)
+    let seed = payload.seed.unwrap_or_else(rand::random);
+    let videos = select_next_videos(
+        store.as_ref(),
+        &config,
+        &indexes,
+        &user_cache,
+        &payload.user_id,
+        &HashSet::new(),
+        seed,
+    )
+    .await?;
+
+    Ok(Json(videos))
+}
+
+async fn select_next_videos(
+    store: &dyn Store,
+    config: &Config,
+    indexes: &Indexes,
+    user_cache: &UserCache,
+    user_id: &UserId,
+    served: &HashSet<VideoId>,
+    seed: u64,
+) -> Result<NextVideosResponse, StatusCode> {
+    let start_time = Instant::now();
+    let (variant, config) = experiment::assign(config, user_id.as_str());
+    let user = user_cache
+        .get(user_id, variant, store, config)
         .await
-        .or_else(|why| {
-            warn!("Fetching user failed: {why}");
-            return Err(StatusCode::NOT_FOUND);
-        })?;
-    
-    let videos = algorithm::next_videos(&user, &config, &db_pool)
+        .map_err(|why| store_error_status("Fetching user failed", why))?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let videos = algorithm::next_videos(&user, config, store, indexes, served, &mut rng)
         .await
-        .or_else(|why| {
-            error!("Next Videos Algorithm failed: {why}");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        })?
+        .map_err(|why| store_error_status("Next Videos Algorithm failed", why))?
         .iter()
         .map(|video| video.uuid.clone())
-        .collect::<Vec<String>>();
+        .collect::<Vec<VideoId>>();
 
     debug!("Processing next videos request took: {} ms", start_time.elapsed().as_millis());
-    Ok(Json(NextVideosResponse { videos }))
+    Ok(NextVideosResponse {
+        videos,
+        cursor: None,
+        seed,
+        variant: variant.to_string(),
+    })
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TrendingResponse {
+    videos: Vec<TrendingVideo>, //Most trending first
+}
+
+//#[debug_handler]
+pub async fn trending(
+    Extension(indexes): Extension<Arc<Indexes>>,
+) -> Result<Json<TrendingResponse>, StatusCode> {
+    let videos = indexes.trending.read().unwrap().videos().to_vec();
+    Ok(Json(TrendingResponse { videos }))
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidateUserRequest {
+    user_id: UserId,
+}
+
+/// Has to be called after the user liked, followed etc. something,
+/// so the next request does not use the outdated cached profile.
+//#[debug_handler]
+pub async fn invalidate_user(
+    Extension(user_cache): Extension<Arc<UserCache>>,
+    Json(payload): Json<InvalidateUserRequest>,
+) -> Result<StatusCode, StatusCode> {
+    if user_cache.invalidate(&payload.user_id) {
+        debug!("Invalidated cached user {}", payload.user_id);
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveVideoRequest {
+    video_id: VideoId,
+}
+
+/// Has to be called after a video was deleted (or hidden), so it is not
+/// scored or sampled from the candidate pool and the score index anymore.
+//#[debug_handler]
+pub async fn remove_video(
+    Extension(indexes): Extension<Arc<Indexes>>,
+    Json(payload): Json<RemoveVideoRequest>,
+) -> Result<StatusCode, StatusCode> {
+    if indexes.remove_video(&payload.video_id) {
+        debug!("Removed video {} from the indexes", payload.video_id);
+    }
+
+    Ok(StatusCode::OK)
+}
+
+//#[debug_handler]
+pub async fn user_cache_stats(
+    Extension(user_cache): Extension<Arc<UserCache>>,
+) -> Result<Json<UserCacheStats>, StatusCode> {
+    Ok(Json(user_cache.stats()))
 }
 
 //#[debug_handler]
@@ -128,14 +446,11 @@ pub async fn get_config(
     Ok(Json((*config.lock().unwrap()).clone()))
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct SetConfigRequest {
-    uuid: String,
-}
-
 //#[debug_handler]
 pub async fn set_config(
     Extension(config): Extension<Arc<Mutex<Config>>>,
+    Extension(indexes): Extension<Arc<Indexes>>,
+    Extension(user_cache): Extension<Arc<UserCache>>,
     Json(payload): Json<Config>,
 ) -> Result<StatusCode, StatusCode> {
     info!("Config update was requested");
@@ -158,7 +473,15 @@ pub async fn set_config(
         }
     }
 
-    *config.lock().unwrap() = payload;
+    let mut config = config.lock().unwrap();
+    if config.schema != payload.schema {
+        warn!("The schema of the new config only takes effect after a restart");
+    }
+
+    let previous = std::mem::replace(&mut *config, payload);
+    indexes.config_changed(&previous, &config);
+    drop(config);
+    user_cache.clear();
     info!("Updated config!");
     Ok(StatusCode::OK)
 }
@@ -0,0 +1,79 @@
+use crate::config::Config;
+
+pub const CONTROL_VARIANT: &str = "control";
+
+/// Assigns the user to an experiment variant and returns its name with the config to use.
+/// The assignment only depends on the user id and the bucket range of the experiment, so
+/// a user stays in the same variant as long as that experiment does not change. Every user is
+/// in at most one experiment (the ranges do not overlap), users outside of all ranges get
+/// the control config. Requests without a user (e.g. the non-personalized scores) always use
+/// the control config, so a treatment that changes the scoring only affects personalized scores.
+pub fn assign<'a>(config: &'a Config, user_id: &str) -> (&'a str, &'a Config) {
+    let bucket = bucket(user_id);
+    config
+        .experiments
+        .iter()
+        .find(|experiment| {
+            bucket >= experiment.bucket_start
+                && bucket < experiment.bucket_start + experiment.traffic_share
+        })
+        .map(|experiment| (experiment.name.as_str(), experiment.treatment.as_ref()))
+        .unwrap_or((CONTROL_VARIANT, config))
+}
+
+/// Maps the user id to a number in [0, 1) with the FNV-1a hash. Unlike the std hasher,
+/// this is guaranteed to stay the same between rust versions and restarts.
+fn bucket(user_id: &str) -> f64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let hash = user_id.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    });
+
+    (hash % 10_000) as f64 / 10_000.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExperimentConfig;
+
+    fn experiment(
+        name: &str,
+        bucket_start: f64,
+        traffic_share: f64,
+        config: &Config,
+    ) -> ExperimentConfig {
+        ExperimentConfig {
+            name: name.to_string(),
+            traffic_share,
+            bucket_start,
+            treatment: Box::new(config.clone()),
+        }
+    }
+
+    #[test]
+    fn adding_an_experiment_does_not_reassign_users() {
+        let control: Config = serde_json::from_str(include_str!("../config.json")).unwrap();
+        let mut config = control.clone();
+        config.experiments = vec![experiment("a", 0.5, 0.5, &control)];
+        let users: Vec<String> = (0..1000).map(|i| format!("user-{i}")).collect();
+        let before: Vec<&str> = users.iter().map(|user| assign(&config, user).0).collect();
+
+        let mut changed = config.clone();
+        changed
+            .experiments
+            .insert(0, experiment("b", 0., 0.25, &control));
+        for (user, variant) in users.iter().zip(before) {
+            let (new_variant, _) = assign(&changed, user);
+            if variant == "a" {
+                assert_eq!(new_variant, "a");
+            } else {
+                assert_ne!(new_variant, "a");
+            }
+        }
+
+        assert!(users.iter().any(|user| assign(&changed, user).0 == "b"));
+    }
+}
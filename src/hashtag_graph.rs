@@ -0,0 +1,78 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// Hashtag co-occurrence: two hashtags are related if they are used on the
+/// same videos. Like the co-like index, the similarity is the cosine of their
+/// occurrences, so hashtags that are on almost every video are not related to everything.
+#[derive(Default)]
+pub struct HashtagGraph {
+    related: HashMap<String, Vec<(String, f64)>>,
+}
+
+impl HashtagGraph {
+    /// Builds the graph from the hashtags of every video and keeps
+    /// the `related_per_hashtag` most related hashtags per hashtag.
+    pub fn build(videos_hashtags: Vec<Vec<String>>, related_per_hashtag: usize) -> Self {
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+        let mut co_counts: HashMap<(&String, &String), usize> = HashMap::new();
+        for hashtags in &videos_hashtags {
+            // A hashtag that is on a video twice only occurs once
+            let hashtags: Vec<&String> = hashtags
+                .iter()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            for (i, hashtag) in hashtags.iter().enumerate() {
+                *counts.entry(hashtag).or_insert(0) += 1;
+                for other in &hashtags[i + 1..] {
+                    *co_counts.entry((hashtag, other)).or_insert(0) += 1;
+                    *co_counts.entry((other, hashtag)).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut related: HashMap<String, Vec<(String, f64)>> = HashMap::new();
+        for ((hashtag, other), count) in co_counts {
+            let similarity = count as f64 / ((counts[hashtag] * counts[other]) as f64).sqrt();
+            related
+                .entry(hashtag.clone())
+                .or_default()
+                .push((other.clone(), similarity));
+        }
+
+        for hashtags in related.values_mut() {
+            hashtags.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+            hashtags.truncate(related_per_hashtag);
+        }
+
+        Self { related }
+    }
+
+    pub fn len(&self) -> usize {
+        self.related.len()
+    }
+
+    /// The most related hashtags with their similarity (0.0 - 1.0), most related first.
+    pub fn related_hashtags(&self, hashtag: &str) -> &[(String, f64)] {
+        self.related
+            .get(hashtag)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashtags(hashtags: &[&str]) -> Vec<String> {
+        hashtags.iter().map(|hashtag| hashtag.to_string()).collect()
+    }
+
+    #[test]
+    fn repeated_hashtags_count_once() {
+        let graph = HashtagGraph::build(vec![hashtags(&["a", "b", "b"]), hashtags(&["b"])], 5);
+        let similarity = graph.related_hashtags("a")[0].1;
+        assert_eq!(graph.related_hashtags("a")[0].0, "b");
+        assert!((similarity - 1. / 2f64.sqrt()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    error::Error,
+    future::Future,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use log::{debug, error};
+use tokio::{sync::Notify, task, time::sleep};
+
+use crate::{
+    candidate_pool::CandidatePool, config::Config, database::VideoId,
+    hashtag_graph::HashtagGraph, score_index::ScoreIndex, similarity::CoLikeIndex, store::Store,
+    trending::TrendingList,
+};
+
+/// Indexes that are built from the whole database in the background
+/// and shared by all requests. They start empty until the first build is done.
+#[derive(Default)]
+pub struct Indexes {
+    pub co_likes: RwLock<CoLikeIndex>,
+    pub hashtag_graph: RwLock<HashtagGraph>,
+    pub trending: RwLock<TrendingList>,
+    pub candidates: RwLock<CandidatePool>,
+    pub scores: RwLock<ScoreIndex>,
+
+    /// Notified when the config section of the index was updated (the score index also
+    /// when the candidate pool was refreshed), wakes up its rebuild loop. A loop that
+    /// is rebuilding keeps the notification for afterwards.
+    co_likes_changed: Notify,
+    hashtag_graph_changed: Notify,
+    trending_changed: Notify,
+    candidates_changed: Notify,
+    scores_changed: Notify,
+}
+
+impl Indexes {
+    /// Rebuilds the indexes whose config sections changed, the others keep their interval.
+    pub fn config_changed(&self, previous: &Config, config: &Config) {
+        if previous.collaborative != config.collaborative {
+            self.co_likes_changed.notify_one();
+        }
+
+        if previous.hashtag_graph != config.hashtag_graph {
+            self.hashtag_graph_changed.notify_one();
+        }
+
+        if previous.trending != config.trending {
+            self.trending_changed.notify_one();
+        }
+
+        if previous.candidate_pool != config.candidate_pool {
+            self.candidates_changed.notify_one();
+        }
+
+        if previous.score_index != config.score_index || previous.scoring != config.scoring {
+            self.scores_changed.notify_one();
+        }
+    }
+
+    /// Removes a deleted (or hidden) video from the candidate pool and the score index,
+    /// so it is neither sampled nor scored from them anymore. Without this, the video
+    /// stays in them until the next refresh of the candidate pool. Returns if it was in one.
+    pub fn remove_video(&self, uuid: &VideoId) -> bool {
+        let in_pool = self.candidates.write().unwrap().remove(uuid);
+        let scored = self.scores.write().unwrap().remove(uuid);
+        in_pool || scored
+    }
+}
+
+/// Spawns the background tasks that periodically rebuild the indexes
+/// with the current config.
+pub fn spawn_rebuilds(indexes: Arc<Indexes>, config: Arc<Mutex<Config>>, store: Arc<dyn Store>) {
+    spawn_rebuild_loop(
+        "co-like index",
+        indexes.clone(),
+        config.clone(),
+        store.clone(),
+        |config| config.collaborative.rebuild_interval_seconds,
+        |indexes| &indexes.co_likes_changed,
+        rebuild_co_likes,
+    );
+
+    spawn_rebuild_loop(
+        "hashtag graph",
+        indexes.clone(),
+        config.clone(),
+        store.clone(),
+        |config| config.hashtag_graph.rebuild_interval_seconds,
+        |indexes| &indexes.hashtag_graph_changed,
+        rebuild_hashtag_graph,
+    );
+
+    spawn_rebuild_loop(
+        "trending list",
+        indexes.clone(),
+        config.clone(),
+        store.clone(),
+        |config| config.trending.rebuild_interval_seconds,
+        |indexes| &indexes.trending_changed,
+        rebuild_trending,
+    );
+
+    spawn_rebuild_loop(
+        "candidate pool",
+        indexes.clone(),
+        config.clone(),
+        store.clone(),
+        |config| config.candidate_pool.refresh_interval_seconds,
+        |indexes| &indexes.candidates_changed,
+        refresh_candidates,
+    );
+
+    spawn_rebuild_loop(
+        "score index",
+        indexes,
+        config,
+        store,
+        |config| config.score_index.rebuild_interval_seconds,
+        |indexes| &indexes.scores_changed,
+        rebuild_scores,
+    );
+}
+
+fn spawn_rebuild_loop<F, Fut>(
+    name: &'static str,
+    indexes: Arc<Indexes>,
+    config: Arc<Mutex<Config>>,
+    store: Arc<dyn Store>,
+    interval_seconds: fn(&Config) -> u64,
+    outdated: fn(&Indexes) -> &Notify,
+    rebuild: F,
+) where
+    F: Fn(Arc<Indexes>, Config, Arc<dyn Store>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send,
+{
+    tokio::spawn(async move {
+        loop {
+            let config = config.lock().unwrap().clone();
+            let start_time = Instant::now();
+            match rebuild(indexes.clone(), config.clone(), store.clone()).await {
+                Ok(()) => debug!(
+                    "Rebuilding {name} took: {} ms",
+                    start_time.elapsed().as_millis()
+                ),
+                Err(why) => error!("Failed to rebuild {name}: {}", why),
+            }
+
+            tokio::select! {
+                _ = sleep(Duration::from_secs(interval_seconds(&config))) => {}
+                _ = outdated(&indexes).notified() => {
+                    debug!("The {name} is outdated, rebuilding it")
+                }
+            }
+        }
+    });
+}
+
+async fn rebuild_co_likes(
+    indexes: Arc<Indexes>,
+    config: Config,
+    store: Arc<dyn Store>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let likes = store
+        .fetch_likes(config.collaborative.max_loaded_likes)
+        .await?;
+
+    let max_likes_per_user = config.collaborative.max_likes_per_user as usize;
+    let related_per_video = config.collaborative.related_videos_per_video as usize;
+    let index = task::spawn_blocking(move || {
+        CoLikeIndex::build(likes, max_likes_per_user, related_per_video)
+    })
+    .await?;
+
+    debug!("Co-like index contains {} videos", index.len());
+    *indexes.co_likes.write().unwrap() = index;
+    Ok(())
+}
+
+async fn rebuild_hashtag_graph(
+    indexes: Arc<Indexes>,
+    config: Config,
+    store: Arc<dyn Store>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let videos_hashtags = store
+        .fetch_videos_hashtags(config.hashtag_graph.max_loaded_videos)
+        .await?;
+
+    let related_per_hashtag = config.hashtag_graph.related_hashtags_per_hashtag as usize;
+    let graph =
+        task::spawn_blocking(move || HashtagGraph::build(videos_hashtags, related_per_hashtag))
+            .await?;
+
+    debug!("Hashtag graph contains {} hashtags", graph.len());
+    *indexes.hashtag_graph.write().unwrap() = graph;
+    Ok(())
+}
+
+async fn rebuild_trending(
+    indexes: Arc<Indexes>,
+    config: Config,
+    store: Arc<dyn Store>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let counts = store.fetch_trending_counts(&config).await?;
+    let trending = TrendingList::build(counts, &config);
+
+    debug!("Trending list contains {} videos", trending.videos().len());
+    *indexes.trending.write().unwrap() = trending;
+    Ok(())
+}
+
+async fn refresh_candidates(
+    indexes: Arc<Indexes>,
+    config: Config,
+    store: Arc<dyn Store>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let videos = store
+        .fetch_ready_videos(config.candidate_pool.max_loaded_videos)
+        .await?;
+    let candidates = task::spawn_blocking(move || CandidatePool::build(videos)).await?;
+
+    debug!("Candidate pool contains {} videos", candidates.len());
+    *indexes.candidates.write().unwrap() = candidates;
+    indexes.scores_changed.notify_one();
+    Ok(())
+}
+
+/// Precomputes the scores of the candidate pool videos, the store is not needed
+/// because the pool already holds the videos with their stats.
+async fn rebuild_scores(
+    indexes: Arc<Indexes>,
+    config: Config,
+    _store: Arc<dyn Store>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let videos = {
+        let candidates = indexes.candidates.read().unwrap();
+        let amount = config.score_index.max_loaded_videos as usize;
+        candidates.videos()[..amount.min(candidates.len())].to_vec()
+    };
+    let scores = task::spawn_blocking(move || ScoreIndex::build(&videos, &config)).await?;
+
+    debug!("Score index contains {} videos", scores.len());
+    *indexes.scores.write().unwrap() = scores;
+    Ok(())
+}
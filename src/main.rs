@@ -6,18 +6,38 @@ use axum::{
     middleware, routing::{get, post}, serve, Extension, Router
 };
 use colored::Colorize;
-use config::{Config, DATABASE_CONN_URL_KEY, HOST_IP_KEY, HOST_PORT_KEY};
+use config::{
+    Config, DATABASE_CONN_URL_KEY, DATABASE_REPLICA_CONN_URLS_KEY, FIXTURE_PATH_KEY, HOST_IP_KEY,
+    HOST_PORT_KEY,
+};
 use dotenv::dotenv;
 use env_logger::{Builder, Env};
-use log::{debug, error, info};
-use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
+use log::{debug, error, info, warn};
+use indexes::Indexes;
+use memory_store::MemoryStore;
+use session::FeedSessions;
+use store::Store;
+use user_cache::UserCache;
 use tokio::net::TcpListener;
 
 mod algorithm;
 mod auth;
+mod candidate_pool;
 mod config;
 mod database;
 mod endpoint;
+mod experiment;
+mod hashtag_graph;
+mod indexes;
+mod memory_store;
+mod replica;
+mod score_index;
+mod scoring;
+mod session;
+mod similarity;
+mod store;
+mod trending;
+mod user_cache;
 
 #[tokio::main]
 async fn main() {
@@ -80,12 +100,32 @@ async fn main() {
         exit(0);
     });
 
-    let db_pool = match connect_db(&config).await {
-        Ok(pool) => pool,
-        Err(why) => {
-            error!("Failed to connect or testquery to database: {}", why);
-            exit(0);
+    let store: Arc<dyn Store> = match env::var(FIXTURE_PATH_KEY) {
+        Ok(path) => match MemoryStore::from_fixture(&path) {
+            Ok(store) => {
+                warn!("Using fixture {path} instead of the database");
+                Arc::new(store)
+            }
+            Err(why) => {
+                error!("Failed to load fixture {path}: {}", why);
+                exit(0);
+            }
+        },
+        Err(_) => match database::connect(&database_url(), &config, None).await {
+            Ok(store) => store,
+            Err(why) => {
+                error!("Failed to connect or testquery to database: {}", why);
+                exit(0);
+            }
+        },
+    };
+
+    let config = Arc::new(Mutex::new(config));
+    let store = match env::var(DATABASE_REPLICA_CONN_URLS_KEY) {
+        Ok(urls) if env::var(FIXTURE_PATH_KEY).is_err() => {
+            replica::connect(store, &urls, config.clone()).await
         }
+        _ => store,
     };
 
     info!(
@@ -93,7 +133,10 @@ async fn main() {
         Instant::elapsed(&start_time).as_millis()
     );
 
-    serve(listener, app(config, db_pool))
+    let indexes = Arc::new(Indexes::default());
+    indexes::spawn_rebuilds(indexes.clone(), config.clone(), store.clone());
+
+    serve(listener, app(config, store, indexes))
         .await
         .unwrap_or_else(|e| {
             error!("Failed to start server: {}", e);
@@ -102,43 +145,43 @@ async fn main() {
 }
 
 
-fn app(config: Config, db_pool: MySqlPool) -> Router {
+fn app(config: Arc<Mutex<Config>>, store: Arc<dyn Store>, indexes: Arc<Indexes>) -> Router {
     let jwt_router = Router::new()
         .route("/scoreVideo", post(endpoint::score_video))
         .route(
             "/scoreVideoPersonalized",
             post(endpoint::score_video_personalized),
         )
+        .route("/scoreVideos", post(endpoint::score_videos))
+        .route(
+            "/scoreVideosPersonalized",
+            post(endpoint::score_videos_personalized),
+        )
         .route("/nextVideos", post(endpoint::next_videos))
+        .route("/trending", get(endpoint::trending))
         .layer(middleware::from_fn(auth::jwt_middleware));
 
     let internal_router = Router::new()
         .route("/getConfig", get(endpoint::get_config))
         .route("/setConfig", post(endpoint::set_config))
+        .route("/explainScore", post(endpoint::explain_score))
+        .route("/replayNextVideos", post(endpoint::replay_next_videos))
+        .route("/invalidateUser", post(endpoint::invalidate_user))
+        .route("/removeVideo", post(endpoint::remove_video))
+        .route("/userCacheStats", get(endpoint::user_cache_stats))
         .layer(middleware::from_fn(auth::internal_secret_middleware));
 
-    let final_router =
-        Router::merge(jwt_router, internal_router)
-        .layer(Extension(Arc::new(Mutex::new(config))))
-        .layer(Extension(Arc::new(db_pool)));
-        
-
-    final_router
+    Router::merge(jwt_router, internal_router)
+        .layer(Extension(config))
+        .layer(Extension(store))
+        .layer(Extension(indexes))
+        .layer(Extension(Arc::new(FeedSessions::new())))
+        .layer(Extension(Arc::new(UserCache::new())))
 }
 
-async fn connect_db(config: &Config) -> Result<MySqlPool, sqlx::Error> {
-    let connection_url = env::var(DATABASE_CONN_URL_KEY).unwrap_or_else(|_| {
+fn database_url() -> String {
+    env::var(DATABASE_CONN_URL_KEY).unwrap_or_else(|_| {
         error!("Failed to get database connection URL from environment");
         exit(0);
-    });
-
-    let pool = MySqlPoolOptions::new()
-        .max_connections(config.max_dbpool_connections)
-        .connect(&connection_url)
-        .await?;
-
-    let row: (String,) = sqlx::query_as("SELECT DATABASE()").fetch_one(&pool).await?;
-
-    info!("Established connection to database: {}", row.0);
-    Ok(pool)
+    })
 }
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, error::Error as StdError, fs};
+
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+
+use crate::{
+    config::Config,
+    database::{User, UserId, Video, VideoId},
+    store::{Store, StoreError, StoreFuture, UserStore, VideoStore},
+    trending::WindowCounts,
+};
+
+/// The content of a fixture file, all videos are treated as ready.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fixture {
+    videos: Vec<Video>,
+    users: HashMap<UserId, User>,
+}
+
+/// A store that holds all videos and users in memory, e.g. loaded from a fixture
+/// file. It answers like the database would, so the algorithm can run (and be
+/// simulated) without MySQL. There are no view and like events, so nothing is trending.
+pub struct MemoryStore {
+    videos: Vec<Video>,
+    users: HashMap<UserId, User>,
+}
+
+impl MemoryStore {
+    pub fn new(videos: Vec<Video>, users: HashMap<UserId, User>) -> Self {
+        Self { videos, users }
+    }
+
+    pub fn from_fixture(path: &str) -> Result<Self, Box<dyn StdError>> {
+        let fixture: Fixture = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self::new(fixture.videos, fixture.users))
+    }
+
+    fn sorted_videos<K: Ord>(
+        &self,
+        filter: impl Fn(&Video) -> bool,
+        key: impl Fn(&Video) -> K,
+        amount: u32,
+    ) -> Vec<Video> {
+        let mut videos: Vec<Video> = self
+            .videos
+            .iter()
+            .filter(|video| filter(video))
+            .cloned()
+            .collect();
+        videos.sort_by_key(|video| std::cmp::Reverse(key(video)));
+        videos.truncate(amount as usize);
+        videos
+    }
+}
+
+impl VideoStore for MemoryStore {
+    fn fetch_video<'a>(&'a self, uuid: &'a VideoId, _config: &'a Config) -> StoreFuture<'a, Video> {
+        let video = self
+            .videos
+            .iter()
+            .find(|video| video.uuid == *uuid)
+            .cloned()
+            .ok_or(StoreError::NotFound);
+        Box::pin(async move { video })
+    }
+
+    fn fetch_videos_by_ids<'a>(&'a self, ids: &'a [VideoId]) -> StoreFuture<'a, Vec<Video>> {
+        let videos = self
+            .videos
+            .iter()
+            .filter(|video| ids.contains(&video.uuid))
+            .cloned()
+            .collect();
+        Box::pin(async move { Ok(videos) })
+    }
+
+    fn fetch_ready_videos(&self, amount: u32) -> StoreFuture<'_, Vec<Video>> {
+        let videos = self.sorted_videos(|_| true, |video| video.created_at, amount);
+        Box::pin(async move { Ok(videos) })
+    }
+
+    fn fetch_fresh_videos<'a>(&'a self, config: &'a Config) -> StoreFuture<'a, Vec<Video>> {
+        let videos = self.sorted_videos(
+            |video| video.views < config.selecting.exploration_max_views as i32,
+            |video| video.created_at,
+            config.selecting.exploration_fetch_amount,
+        );
+        Box::pin(async move { Ok(videos) })
+    }
+
+    fn fetch_cold_start_videos<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> StoreFuture<'a, (Vec<Video>, Vec<Video>)> {
+        let popular = self.sorted_videos(
+            |_| true,
+            |video| video.upvotes,
+            config.cold_start.popular_fetch_amount,
+        );
+
+        let min_created_at =
+            Utc::now() - Duration::hours(config.cold_start.trending_max_age_hours as i64);
+        let trending = self.sorted_videos(
+            |video| video.created_at >= min_created_at,
+            |video| video.views,
+            config.cold_start.trending_fetch_amount,
+        );
+
+        Box::pin(async move { Ok((popular, trending)) })
+    }
+
+    fn fetch_likes(&self, amount: u32) -> StoreFuture<'_, Vec<(UserId, VideoId)>> {
+        let likes = self
+            .users
+            .iter()
+            .flat_map(|(user_id, user)| {
+                user.liked_videos
+                    .iter()
+                    .map(move |video_id| (user_id.clone(), video_id.clone()))
+            })
+            .take(amount as usize)
+            .collect();
+        Box::pin(async move { Ok(likes) })
+    }
+
+    fn fetch_videos_hashtags(&self, amount: u32) -> StoreFuture<'_, Vec<Vec<String>>> {
+        let hashtags = self
+            .videos
+            .iter()
+            .take(amount as usize)
+            .map(|video| video.hashtags.clone())
+            .collect();
+        Box::pin(async move { Ok(hashtags) })
+    }
+
+    fn fetch_trending_counts<'a>(
+        &'a self,
+        _config: &'a Config,
+    ) -> StoreFuture<'a, HashMap<VideoId, WindowCounts>> {
+        Box::pin(async move { Ok(HashMap::new()) })
+    }
+}
+
+impl UserStore for MemoryStore {
+    fn fetch_user<'a>(&'a self, uuid: &'a UserId, config: &'a Config) -> StoreFuture<'a, User> {
+        // Like in the database, an unknown user is a user without any interactions
+        let mut user = self.users.get(uuid).cloned().unwrap_or_default();
+        user.last_hashtags
+            .truncate(config.selecting.user_hashtag_fetch_amount as usize);
+        user.last_viewed
+            .truncate(config.selecting.already_viewed_videos_fetch_amount as usize);
+        Box::pin(async move { Ok(user) })
+    }
+}
+
+impl Store for MemoryStore {
+    fn ping(&self) -> StoreFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
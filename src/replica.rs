@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use tokio::time::{sleep, timeout};
+
+use crate::{
+    config::Config,
+    database::{self, User, UserId, Video, VideoId},
+    store::{Store, StoreError, StoreFuture, UserStore, VideoStore},
+    trending::WindowCounts,
+};
+
+/// The kind of a read, the replica policy decides per kind if it goes to the replicas.
+#[derive(Clone, Copy)]
+enum Reads {
+    Candidates,
+    Profiles,
+}
+
+struct Replica {
+    /// Only the position of the url, the url itself contains the password.
+    name: String,
+    url: String,
+    /// Set once the replica is connected, a replica that can not be connected
+    /// to on startup is connected by the health checks later.
+    store: OnceLock<Arc<dyn Store>>,
+    healthy: AtomicBool,
+}
+
+impl Replica {
+    /// Connects to the replica, the pool does not wait longer than the timeout
+    /// for a connection.
+    async fn connect(&self, config: &Config) {
+        let acquire_timeout = Some(Duration::from_millis(config.replicas.timeout_millis));
+        match database::connect(&self.url, config, acquire_timeout).await {
+            Ok(store) => {
+                if self.store.set(store).is_ok() {
+                    self.healthy.store(true, Ordering::Relaxed);
+                    info!("Connected to read replica {}", self.name);
+                }
+            }
+            Err(why) => error!("Failed to connect to read replica {}: {why}", self.name),
+        }
+    }
+
+    fn mark_unhealthy(&self, why: &StoreError) {
+        if self.healthy.swap(false, Ordering::Relaxed) {
+            warn!("Read replica {} is unhealthy, falling back: {why}", self.name);
+        }
+    }
+}
+
+/// Sends the reads the replica policy allows to the read replicas (in turns) and
+/// everything else to the primary. A replica that can not be reached (in time) is marked
+/// unhealthy and the read is repeated on the primary, the replica gets reads again
+/// once a background health check succeeds.
+pub struct ReplicatedStore {
+    primary: Arc<dyn Store>,
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+    config: Arc<Mutex<Config>>,
+}
+
+/// Connects to the read replicas and wraps the primary store, so the replica policy
+/// of the config is applied. Replicas that can not be connected to on startup are
+/// connected by the health checks later, without any replica url the primary store
+/// is returned as it is.
+pub async fn connect(
+    primary: Arc<dyn Store>,
+    urls: &str,
+    config: Arc<Mutex<Config>>,
+) -> Arc<dyn Store> {
+    let replicas: Vec<Replica> = urls
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .enumerate()
+        .map(|(i, url)| Replica {
+            name: format!("#{}", i + 1),
+            url: url.to_string(),
+            store: OnceLock::new(),
+            healthy: AtomicBool::new(false),
+        })
+        .collect();
+
+    if replicas.is_empty() {
+        warn!("No read replica is set, all reads go to the primary database");
+        return primary;
+    }
+
+    let startup_config = config.lock().unwrap().clone();
+    for replica in &replicas {
+        replica.connect(&startup_config).await;
+    }
+
+    let connected = replicas.iter().filter(|replica| replica.store.get().is_some()).count();
+    info!("Using {connected} of {} read replica(s)", replicas.len());
+    let store = Arc::new(ReplicatedStore {
+        primary,
+        replicas,
+        next: AtomicUsize::new(0),
+        config,
+    });
+    spawn_health_checks(store.clone());
+    store
+}
+
+/// Pings every replica periodically, so unhealthy replicas get reads again
+/// once they are back and replicas that went away are noticed before a read fails.
+/// Replicas that are not connected yet are connected again.
+fn spawn_health_checks(store: Arc<ReplicatedStore>) {
+    tokio::spawn(async move {
+        loop {
+            let config = store.config.lock().unwrap().clone();
+            sleep(Duration::from_secs(config.replicas.health_check_interval_seconds)).await;
+
+            for replica in &store.replicas {
+                let Some(replica_store) = replica.store.get() else {
+                    replica.connect(&config).await;
+                    continue;
+                };
+
+                match store.within_timeout(replica_store.ping()).await {
+                    Ok(()) => {
+                        if !replica.healthy.swap(true, Ordering::Relaxed) {
+                            info!("Read replica {} is healthy again", replica.name);
+                        }
+                    }
+                    Err(why) => replica.mark_unhealthy(&why),
+                }
+            }
+        }
+    });
+}
+
+impl ReplicatedStore {
+    /// The next healthy replica for the reads, if the policy sends them to the replicas.
+    fn replica(&self, reads: Reads) -> Option<(&Replica, &dyn Store)> {
+        let routed = {
+            let policy = &self.config.lock().unwrap().replicas;
+            match reads {
+                Reads::Candidates => policy.candidate_reads,
+                Reads::Profiles => policy.profile_reads,
+            }
+        };
+        if !routed {
+            return None;
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..self.replicas.len())
+            .map(|i| &self.replicas[(start + i) % self.replicas.len()])
+            .filter(|replica| replica.healthy.load(Ordering::Relaxed))
+            .find_map(|replica| Some((replica, replica.store.get()?.as_ref())))
+    }
+
+    /// Fails a replica call that takes longer than the timeout as unavailable.
+    async fn within_timeout<T>(
+        &self,
+        call: impl Future<Output = Result<T, StoreError>>,
+    ) -> Result<T, StoreError> {
+        let limit = Duration::from_millis(self.config.lock().unwrap().replicas.timeout_millis);
+        timeout(limit, call).await.unwrap_or_else(|_| {
+            Err(StoreError::Unavailable(sqlx::Error::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the read replica did not answer in time",
+            ))))
+        })
+    }
+
+    /// Runs the read on a replica or the primary, see `ReplicatedStore`.
+    fn read<'a, T: 'a>(
+        &'a self,
+        reads: Reads,
+        query: impl Fn(&'a dyn Store) -> StoreFuture<'a, T> + Send + 'a,
+    ) -> StoreFuture<'a, T> {
+        Box::pin(async move {
+            if let Some((replica, store)) = self.replica(reads) {
+                match self.within_timeout(query(store)).await {
+                    Err(why @ StoreError::Unavailable(_)) => replica.mark_unhealthy(&why),
+                    result => return result,
+                }
+            }
+
+            query(self.primary.as_ref()).await
+        })
+    }
+}
+
+impl VideoStore for ReplicatedStore {
+    fn fetch_video<'a>(&'a self, uuid: &'a VideoId, config: &'a Config) -> StoreFuture<'a, Video> {
+        self.read(Reads::Candidates, move |store| store.fetch_video(uuid, config))
+    }
+
+    fn fetch_videos_by_ids<'a>(&'a self, ids: &'a [VideoId]) -> StoreFuture<'a, Vec<Video>> {
+        self.read(Reads::Candidates, move |store| store.fetch_videos_by_ids(ids))
+    }
+
+    fn fetch_ready_videos(&self, amount: u32) -> StoreFuture<'_, Vec<Video>> {
+        self.read(Reads::Candidates, move |store| store.fetch_ready_videos(amount))
+    }
+
+    fn fetch_fresh_videos<'a>(&'a self, config: &'a Config) -> StoreFuture<'a, Vec<Video>> {
+        self.read(Reads::Candidates, move |store| store.fetch_fresh_videos(config))
+    }
+
+    fn fetch_cold_start_videos<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> StoreFuture<'a, (Vec<Video>, Vec<Video>)> {
+        self.read(Reads::Candidates, move |store| store.fetch_cold_start_videos(config))
+    }
+
+    fn fetch_likes(&self, amount: u32) -> StoreFuture<'_, Vec<(UserId, VideoId)>> {
+        self.read(Reads::Candidates, move |store| store.fetch_likes(amount))
+    }
+
+    fn fetch_videos_hashtags(&self, amount: u32) -> StoreFuture<'_, Vec<Vec<String>>> {
+        self.read(Reads::Candidates, move |store| store.fetch_videos_hashtags(amount))
+    }
+
+    fn fetch_trending_counts<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> StoreFuture<'a, HashMap<VideoId, WindowCounts>> {
+        self.read(Reads::Candidates, move |store| store.fetch_trending_counts(config))
+    }
+}
+
+impl UserStore for ReplicatedStore {
+    fn fetch_user<'a>(&'a self, uuid: &'a UserId, config: &'a Config) -> StoreFuture<'a, User> {
+        self.read(Reads::Profiles, move |store| store.fetch_user(uuid, config))
+    }
+}
+
+impl Store for ReplicatedStore {
+    fn ping(&self) -> StoreFuture<'_, ()> {
+        self.primary.ping()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+
+    /// A replica that is connected but can not be reached anymore.
+    struct UnreachableStore;
+
+    fn unavailable<'a, T>() -> StoreFuture<'a, T> {
+        Box::pin(async { Err(StoreError::Unavailable(sqlx::Error::PoolTimedOut)) })
+    }
+
+    impl VideoStore for UnreachableStore {
+        fn fetch_video<'a>(&'a self, _: &'a VideoId, _: &'a Config) -> StoreFuture<'a, Video> {
+            unavailable()
+        }
+
+        fn fetch_videos_by_ids<'a>(&'a self, _: &'a [VideoId]) -> StoreFuture<'a, Vec<Video>> {
+            unavailable()
+        }
+
+        fn fetch_ready_videos(&self, _: u32) -> StoreFuture<'_, Vec<Video>> {
+            unavailable()
+        }
+
+        fn fetch_fresh_videos<'a>(&'a self, _: &'a Config) -> StoreFuture<'a, Vec<Video>> {
+            unavailable()
+        }
+
+        fn fetch_cold_start_videos<'a>(
+            &'a self,
+            _: &'a Config,
+        ) -> StoreFuture<'a, (Vec<Video>, Vec<Video>)> {
+            unavailable()
+        }
+
+        fn fetch_likes(&self, _: u32) -> StoreFuture<'_, Vec<(UserId, VideoId)>> {
+            unavailable()
+        }
+
+        fn fetch_videos_hashtags(&self, _: u32) -> StoreFuture<'_, Vec<Vec<String>>> {
+            unavailable()
+        }
+
+        fn fetch_trending_counts<'a>(
+            &'a self,
+            _: &'a Config,
+        ) -> StoreFuture<'a, HashMap<VideoId, WindowCounts>> {
+            unavailable()
+        }
+    }
+
+    impl UserStore for UnreachableStore {
+        fn fetch_user<'a>(&'a self, _: &'a UserId, _: &'a Config) -> StoreFuture<'a, User> {
+            unavailable()
+        }
+    }
+
+    impl Store for UnreachableStore {
+        fn ping(&self) -> StoreFuture<'_, ()> {
+            unavailable()
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_fall_back_to_the_primary_when_the_replica_is_unavailable() {
+        let config: Config = serde_json::from_str(include_str!("../config.json")).unwrap();
+        assert!(config.replicas.candidate_reads);
+        let primary = Arc::new(MemoryStore::from_fixture("fixtures/example.json").unwrap());
+        let replica = Replica {
+            name: "#1".to_string(),
+            url: String::new(),
+            store: OnceLock::new(),
+            healthy: AtomicBool::new(true),
+        };
+        let _ = replica.store.set(Arc::new(UnreachableStore));
+        let store = ReplicatedStore {
+            primary: primary.clone(),
+            replicas: vec![replica],
+            next: AtomicUsize::new(0),
+            config: Arc::new(Mutex::new(config)),
+        };
+
+        let videos = store.fetch_ready_videos(u32::MAX).await.unwrap();
+        let expected = primary.fetch_ready_videos(u32::MAX).await.unwrap();
+        assert!(!videos.is_empty());
+        assert_eq!(
+            videos.iter().map(|video| &video.uuid).collect::<Vec<_>>(),
+            expected.iter().map(|video| &video.uuid).collect::<Vec<_>>()
+        );
+        assert!(!store.replicas[0].healthy.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use crate::{
+    algorithm,
+    config::{Config, ScoringConfig},
+    database::{Video, VideoId},
+};
+
+/// The precomputed (global) scores of the candidate pool videos. The scores are only
+/// valid for the scoring config they were computed with, so requests with another
+/// scoring config (e.g. an experiment or right after a config update) miss.
+#[derive(Default)]
+pub struct ScoreIndex {
+    scoring: Option<ScoringConfig>,
+    scores: HashMap<VideoId, f64>,
+}
+
+impl ScoreIndex {
+    pub fn build(videos: &[Video], config: &Config) -> Self {
+        let scores = videos
+            .iter()
+            .map(|video| (video.uuid.clone(), algorithm::score_video(video, config)))
+            .collect();
+
+        Self {
+            scoring: Some(config.scoring.clone()),
+            scores,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// The precomputed score of the video, if it was computed with the scoring config.
+    pub fn get(&self, uuid: &VideoId, config: &Config) -> Option<f64> {
+        if self.scoring.as_ref() != Some(&config.scoring) {
+            return None;
+        }
+
+        self.scores.get(uuid).copied()
+    }
+
+    /// Removes the score of the video, returns if it had one.
+    pub fn remove(&mut self, uuid: &VideoId) -> bool {
+        self.scores.remove(uuid).is_some()
+    }
+}
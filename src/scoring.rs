@@ -0,0 +1,341 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    database::{User, Video},
+};
+
+pub const DEFAULT_SCORER: &str = "default";
+pub const BAYESIAN_SCORER: &str = "bayesian";
+
+/// The result of scoring a video with the contribution of every factor.
+/// Multipliers that were not applied (e.g. no views yet) are `None`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreExplanation {
+    /// Added to the base score of 1.0
+    pub upvote_term: f64,
+    pub view_term: f64,
+
+    // Multiplied with the score
+    pub like_2_view: Option<f64>,
+    pub viewtime: Option<f64>,
+    pub upvote_ratio: Option<f64>,
+    pub downvote_impact: Option<f64>,
+    pub comments_ratio: Option<f64>,
+
+    /// The score right before it was normalized towards the viral score
+    pub pre_normalization_score: f64,
+    pub normalized_score: f64,
+
+    pub freshness: Option<f64>,
+    pub follow_multiplier: Option<f64>,
+    pub liked_multiplier: Option<f64>,
+    pub downvoted_multiplier: Option<f64>,
+    pub not_interested_hashtag_multiplier: Option<f64>,
+    pub not_interested_creator_multiplier: Option<f64>,
+
+    /// The final score
+    pub score: f64,
+}
+
+impl ScoreExplanation {
+    fn multiply(&mut self, factor: f64) -> Option<f64> {
+        self.score *= factor;
+        Some(factor)
+    }
+
+    pub fn apply_freshness(&mut self, factor: f64) {
+        self.freshness = self.multiply(factor);
+    }
+}
+
+/// A scoring strategy. Implementations are selected by name through
+/// the `scorer` value of the scoring config, see [`by_name`].
+pub trait Scorer: Send + Sync {
+    /// Scores a video just by its stats (global score).
+    fn explain(&self, video: &Video, config: &Config) -> ScoreExplanation;
+
+    /// Scores a video for a specific viewer. By default this is the global
+    /// score multiplied by the viewer related multipliers.
+    fn explain_personalized(
+        &self,
+        user: &User,
+        video: &Video,
+        config: &Config,
+    ) -> ScoreExplanation {
+        let mut explanation = self.explain(video, config);
+        self.personalize(&mut explanation, user, video, config);
+        explanation
+    }
+
+    /// Applies the viewer related multipliers to an already scored video.
+    fn personalize(
+        &self,
+        explanation: &mut ScoreExplanation,
+        user: &User,
+        video: &Video,
+        config: &Config,
+    ) {
+        if user.following.contains(&video.user_id) {
+            explanation.follow_multiplier =
+                explanation.multiply(config.scoring.viewer_following_creator_multiplier);
+        }
+
+        //Do not wanna show exact same videos
+        if user.liked_videos.contains(&video.uuid) {
+            explanation.liked_multiplier =
+                explanation.multiply(config.scoring.viewer_liked_video_multiplier);
+        }
+
+        if user.downvoted_videos.contains(&video.uuid) {
+            explanation.downvoted_multiplier =
+                explanation.multiply(config.scoring.viewer_downvoted_video_multiplier);
+        }
+
+        if video
+            .hashtags
+            .iter()
+            .any(|hashtag| user.not_interested_hashtags.contains(hashtag))
+        {
+            explanation.not_interested_hashtag_multiplier =
+                explanation.multiply(config.scoring.viewer_not_interested_hashtag_multiplier);
+        }
+
+        if user.not_interested_creators.contains(&video.user_id) {
+            explanation.not_interested_creator_multiplier =
+                explanation.multiply(config.scoring.viewer_not_interested_creator_multiplier);
+        }
+    }
+}
+
+/// Returns the scorer registered under the given name.
+pub fn by_name(name: &str) -> Option<&'static dyn Scorer> {
+    match name {
+        DEFAULT_SCORER => Some(&DefaultScorer),
+        BAYESIAN_SCORER => Some(&BayesianScorer),
+        _ => None,
+    }
+}
+
+/// The ratios the score gets multiplied with. A `None` ratio
+/// is skipped, for example when there are no views yet.
+struct Ratios {
+    like_2_view: Option<f64>,
+    viewtime_per_view: Option<f64>,
+    upvote_2_totalvotes: Option<f64>,
+    downvote_2_views: Option<f64>,
+    comments_2_votes: Option<f64>,
+}
+
+/// The formula both built-in scorers share: likes and views raise the score
+/// exponentially and the ratios (like2view, viewtime, upvotes, comments) multiply it.
+fn compose_score(video: &Video, ratios: &Ratios, config: &Config) -> ScoreExplanation {
+    let mut explanation = ScoreExplanation {
+        //Score up with likes
+        upvote_term: (video.upvotes as f64 / 10.).powf(config.scoring.upvote_exponent),
+
+        //Score up with views
+        view_term: (video.views as f64 / 10.).powf(config.scoring.view_exponent),
+        ..Default::default()
+    };
+    explanation.score = 1. + explanation.upvote_term + explanation.view_term;
+
+    //Score multiplied by like-2-view ratio
+    if let Some(ratio) = ratios.like_2_view {
+        explanation.like_2_view = explanation.multiply(ratio * config.scoring.like_2_view_strength);
+    }
+
+    //Multiply score by avg. viewtime per view
+    if let Some(ratio) = ratios.viewtime_per_view {
+        explanation.viewtime =
+            explanation.multiply(ratio * config.scoring.viewtime_per_view_strength);
+    }
+
+    //Multiply score by upvote-2-totalvotes ratio
+    if let Some(ratio) = ratios.upvote_2_totalvotes {
+        explanation.upvote_ratio =
+            explanation.multiply(ratio * config.scoring.upvote_2_totalvotes_strength);
+    }
+
+    //Multiply score by downvote-2-views impact
+    if let Some(impact) = ratios.downvote_2_views {
+        explanation.downvote_impact = explanation.multiply((1.0 - impact).max(0.5));
+    }
+
+    if let Some(ratio) = ratios.comments_2_votes {
+        explanation.comments_ratio =
+            explanation.multiply(ratio * config.scoring.comments_2_votes_strength);
+    }
+
+    explanation.pre_normalization_score = explanation.score;
+    normalize_score(
+        &mut explanation.score,
+        &config.scoring.viral_score,
+        config.scoring.normalize_threshold,
+    );
+    explanation.normalized_score = explanation.score;
+
+    explanation
+}
+
+/// The original formula with the raw ratios of the video stats.
+pub struct DefaultScorer;
+
+impl Scorer for DefaultScorer {
+    fn explain(&self, video: &Video, config: &Config) -> ScoreExplanation {
+        let views = video.views as f64;
+        let total_votes = video.upvotes + video.downvotes;
+        let voted = video.views != 0 && total_votes != 0;
+
+        let ratios = Ratios {
+            like_2_view: (video.views != 0).then(|| video.upvotes as f64 / views),
+            viewtime_per_view: (video.views != 0).then(|| video.viewtime_seconds as f64 / views),
+            upvote_2_totalvotes: voted.then(|| video.upvotes as f64 / total_votes as f64),
+            downvote_2_views: voted.then(|| video.downvotes as f64 / views),
+            //less votes per comment = better
+            comments_2_votes: (video.comments != 0)
+                .then(|| video.comments as f64 / total_votes as f64),
+        };
+
+        compose_score(video, &ratios, config)
+    }
+}
+
+/// The same formula as the default scorer, but every ratio is a bayesian average:
+/// the stats are mixed with `prior_strength` imaginary views/votes that have the
+/// configured prior ratios. A video with 1 view and 1 upvote therefore is close
+/// to the prior instead of having a perfect ratio, the more stats a video gets
+/// the more its real ratios count.
+pub struct BayesianScorer;
+
+fn bayesian_average(hits: f64, trials: f64, prior: f64, prior_strength: f64) -> f64 {
+    (hits + prior * prior_strength) / (trials + prior_strength)
+}
+
+impl Scorer for BayesianScorer {
+    fn explain(&self, video: &Video, config: &Config) -> ScoreExplanation {
+        let c = &config.scoring;
+        let views = video.views as f64;
+        let total_votes = (video.upvotes + video.downvotes) as f64;
+
+        let ratios = Ratios {
+            like_2_view: Some(bayesian_average(
+                video.upvotes as f64,
+                views,
+                c.prior_like_2_view_ratio,
+                c.prior_strength,
+            )),
+            viewtime_per_view: Some(bayesian_average(
+                video.viewtime_seconds as f64,
+                views,
+                c.prior_viewtime_per_view,
+                c.prior_strength,
+            )),
+            upvote_2_totalvotes: Some(bayesian_average(
+                video.upvotes as f64,
+                total_votes,
+                c.prior_upvote_2_totalvotes_ratio,
+                c.prior_strength,
+            )),
+            // Prior of no downvotes, so few views can not cause a big impact
+            downvote_2_views: Some(bayesian_average(
+                video.downvotes as f64,
+                views,
+                0.,
+                c.prior_strength,
+            )),
+            comments_2_votes: Some(bayesian_average(
+                video.comments as f64,
+                total_votes,
+                c.prior_comments_2_votes_ratio,
+                c.prior_strength,
+            )),
+        };
+
+        compose_score(video, &ratios, config)
+    }
+}
+
+/// The multiplier for the age of a video: 1.0 for a brand new video, halved
+/// every `freshness_half_life_hours`.
+pub fn freshness_factor(video: &Video, config: &Config) -> f64 {
+    let half_life = config.scoring.freshness_half_life_hours;
+    if half_life <= 0. {
+        return 1.;
+    }
+
+    let age_hours = (Utc::now() - video.created_at).num_seconds().max(0) as f64 / 3600.;
+    0.5_f64.powf(age_hours / half_life)
+}
+
+pub fn normalize_score(score: &mut f64, target: &f64, threshold: f64) {
+    let threshold = threshold.clamp(0.0, 1.0);
+    let ratio = *score / target;
+
+    if ratio > 1.0 {
+        *score = target * (1.0 + (ratio - 1.0) * (1.0 - threshold));
+    } else {
+        *score = target * (1.0 - (1.0 - ratio) * (1.0 - threshold));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{
+        algorithm,
+        database::{UserId, VideoId},
+    };
+
+    fn config() -> Config {
+        let mut config: Config = serde_json::from_str(include_str!("../config.json")).unwrap();
+        config.scoring.scorer = BAYESIAN_SCORER.to_string();
+        config
+    }
+
+    fn video(upvotes: i32, downvotes: i32, views: i32, comments: i32, age_hours: i64) -> Video {
+        Video {
+            uuid: VideoId::parse("00000000-0000-0000-0000-00000000000a").unwrap(),
+            user_id: UserId::parse("00000000-0000-0000-0000-000000000002").unwrap(),
+            upvotes,
+            downvotes,
+            views,
+            comments,
+            viewtime_seconds: views as i64 * 12,
+            created_at: Utc::now() - Duration::hours(age_hours),
+            hashtags: Vec::new(),
+            score: 0.,
+        }
+    }
+
+    #[test]
+    fn one_upvote_and_view_does_not_outrank_an_established_video() {
+        let config = config();
+        let lucky = video(1, 0, 1, 0, 0);
+        let established = video(300, 10, 2000, 30, 0);
+
+        let lucky_score = algorithm::score_video(&lucky, &config);
+        let established_score = algorithm::score_video(&established, &config);
+        assert!(
+            lucky_score < established_score,
+            "{lucky_score} >= {established_score}"
+        );
+    }
+
+    #[test]
+    fn freshness_is_applied_on_top_of_the_bayesian_score() {
+        let config = config();
+        let half_life = config.scoring.freshness_half_life_hours as i64;
+        let new = algorithm::explain_score(&video(300, 10, 2000, 30, 0), &config);
+        let old = algorithm::explain_score(&video(300, 10, 2000, 30, half_life), &config);
+
+        // Same stats, so the same score before the freshness
+        assert!((new.normalized_score - old.normalized_score).abs() < 1e-9);
+        assert!((old.freshness.unwrap() - 0.5).abs() < 1e-3);
+        assert!((old.score / new.score - 0.5).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{
+    config::Config,
+    database::{UserId, VideoId},
+};
+
+struct FeedSession {
+    user_id: UserId,
+    served: HashSet<VideoId>,
+    last_used: Instant,
+}
+
+/// Remembers which videos were already served to a user while scrolling,
+/// so the next page of the feed does not repeat them. A session is identified
+/// by the cursor that is sent back and forth with every next videos request.
+pub struct FeedSessions {
+    sessions: Mutex<HashMap<String, FeedSession>>,
+}
+
+impl FeedSessions {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cursor to continue with and the videos that were already served
+    /// in that session. Unknown or expired cursors and cursors of another user
+    /// start a new session.
+    pub fn resume(
+        &self,
+        cursor: Option<&str>,
+        user_id: &UserId,
+        config: &Config,
+    ) -> (String, HashSet<VideoId>) {
+        let ttl = Duration::from_secs(config.selecting.feed_session_ttl_seconds);
+        let mut sessions = self.sessions.lock().unwrap();
+
+        if let Some((cursor, session)) = cursor.and_then(|cursor| sessions.get_key_value(cursor)) {
+            if session.user_id == *user_id && session.last_used.elapsed() < ttl {
+                return (cursor.clone(), session.served.clone());
+            }
+        }
+
+        sessions.retain(|_, session| session.last_used.elapsed() < ttl);
+        debug!("Starting new feed session ({} active)", sessions.len() + 1);
+
+        let cursor = format!("{:032x}", rand::random::<u128>());
+        sessions.insert(
+            cursor.clone(),
+            FeedSession {
+                user_id: user_id.clone(),
+                served: HashSet::new(),
+                last_used: Instant::now(),
+            },
+        );
+
+        (cursor, HashSet::new())
+    }
+
+    /// Adds the videos to the served videos of the session.
+    pub fn record(&self, cursor: &str, videos: &[VideoId]) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(cursor) {
+            session.served.extend(videos.iter().cloned());
+            session.last_used = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use crate::database::{UserId, VideoId};
+
+/// Item-to-item similarity from likes: two videos are related if the same users
+/// liked both of them. The similarity is the cosine of their like vectors, so
+/// videos everyone likes are not related to everything.
+#[derive(Default)]
+pub struct CoLikeIndex {
+    related: HashMap<VideoId, Vec<(VideoId, f64)>>,
+}
+
+impl CoLikeIndex {
+    /// Builds the index from (user id, video id) likes. Only the first `max_likes_per_user`
+    /// likes of a user are paired, because the pairs grow quadratically.
+    pub fn build(
+        likes: Vec<(UserId, VideoId)>,
+        max_likes_per_user: usize,
+        related_per_video: usize,
+    ) -> Self {
+        let mut likes_by_user: HashMap<UserId, Vec<VideoId>> = HashMap::new();
+        for (user_id, video_id) in likes {
+            let user_likes = likes_by_user.entry(user_id).or_default();
+            if user_likes.len() < max_likes_per_user && !user_likes.contains(&video_id) {
+                user_likes.push(video_id);
+            }
+        }
+
+        let mut like_counts: HashMap<&VideoId, usize> = HashMap::new();
+        let mut co_like_counts: HashMap<(&VideoId, &VideoId), usize> = HashMap::new();
+        for user_likes in likes_by_user.values() {
+            for (i, video) in user_likes.iter().enumerate() {
+                *like_counts.entry(video).or_insert(0) += 1;
+                for other in &user_likes[i + 1..] {
+                    *co_like_counts.entry((video, other)).or_insert(0) += 1;
+                    *co_like_counts.entry((other, video)).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut related: HashMap<VideoId, Vec<(VideoId, f64)>> = HashMap::new();
+        for ((video, other), count) in co_like_counts {
+            let similarity =
+                count as f64 / ((like_counts[video] * like_counts[other]) as f64).sqrt();
+            related
+                .entry(video.clone())
+                .or_default()
+                .push((other.clone(), similarity));
+        }
+
+        for videos in related.values_mut() {
+            videos.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+            videos.truncate(related_per_video);
+        }
+
+        Self { related }
+    }
+
+    pub fn len(&self) -> usize {
+        self.related.len()
+    }
+
+    /// The videos most related to the given (liked) videos, without the given videos
+    /// themselves. A video related to multiple given videos sums up the similarities.
+    pub fn related_videos(&self, videos: &[VideoId], amount: usize) -> Vec<VideoId> {
+        let mut similarities: HashMap<&VideoId, f64> = HashMap::new();
+        for video in videos {
+            for (other, similarity) in self.related.get(video).into_iter().flatten() {
+                *similarities.entry(other).or_insert(0.) += similarity;
+            }
+        }
+
+        let mut related: Vec<(&VideoId, f64)> = similarities
+            .into_iter()
+            .filter(|(video, _)| !videos.contains(video))
+            .collect();
+        related.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(b.0)));
+
+        related
+            .into_iter()
+            .take(amount)
+            .map(|(video, _)| video.clone())
+            .collect()
+    }
+}
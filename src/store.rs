@@ -0,0 +1,111 @@
+use std::{collections::HashMap, error::Error, fmt, future::Future, pin::Pin};
+
+use crate::{
+    config::Config,
+    database::{User, UserId, Video, VideoId},
+    trending::WindowCounts,
+};
+
+/// Why a store could not answer, so the endpoints can respond with a fitting status code.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The video does not exist or is not ready.
+    NotFound,
+    /// A row could not be decoded, e.g. a malformed id or a column with an unexpected type.
+    Decode(sqlx::Error),
+    /// The database can not be reached (in time).
+    Unavailable(sqlx::Error),
+    /// Any other database error, e.g. a query the database rejected.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => Self::NotFound,
+            sqlx::Error::ColumnDecode { .. }
+            | sqlx::Error::Decode(_)
+            | sqlx::Error::ColumnNotFound(_)
+            | sqlx::Error::ColumnIndexOutOfBounds { .. }
+            | sqlx::Error::TypeNotFound { .. } => Self::Decode(error),
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_) => Self::Unavailable(error),
+            error => Self::Database(error),
+        }
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "not found"),
+            Self::Decode(error) => write!(f, "malformed row: {error}"),
+            Self::Unavailable(error) => write!(f, "database unavailable: {error}"),
+            Self::Database(error) => write!(f, "database error: {error}"),
+        }
+    }
+}
+
+impl Error for StoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NotFound => None,
+            Self::Decode(error) | Self::Unavailable(error) | Self::Database(error) => Some(error),
+        }
+    }
+}
+
+/// The futures of the stores are boxed, so the stores can be used as trait objects.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, StoreError>> + Send + 'a>>;
+
+/// Boxes a database query, its errors are classified as store errors.
+pub fn boxed<'a, T>(
+    future: impl Future<Output = Result<T, sqlx::Error>> + Send + 'a,
+) -> StoreFuture<'a, T> {
+    Box::pin(async move { Ok(future.await?) })
+}
+
+/// Everything the algorithm and the background indexes read about videos.
+/// All returned videos are ready (visible), unknown or not ready videos are skipped.
+pub trait VideoStore: Send + Sync {
+    fn fetch_video<'a>(&'a self, uuid: &'a VideoId, config: &'a Config) -> StoreFuture<'a, Video>;
+
+    fn fetch_videos_by_ids<'a>(&'a self, ids: &'a [VideoId]) -> StoreFuture<'a, Vec<Video>>;
+
+    /// The newest ready videos (up to the amount), for the candidate pool.
+    fn fetch_ready_videos(&self, amount: u32) -> StoreFuture<'_, Vec<Video>>;
+
+    /// Videos that do not have enough views for reliable stats yet, newest first.
+    fn fetch_fresh_videos<'a>(&'a self, config: &'a Config) -> StoreFuture<'a, Vec<Video>>;
+
+    /// Popular and trending videos for the cold start.
+    fn fetch_cold_start_videos<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> StoreFuture<'a, (Vec<Video>, Vec<Video>)>;
+
+    /// (user id, video id) pairs of all likes, for the co-like index.
+    fn fetch_likes(&self, amount: u32) -> StoreFuture<'_, Vec<(UserId, VideoId)>>;
+
+    /// The hashtags of the ready videos, for the hashtag graph.
+    fn fetch_videos_hashtags(&self, amount: u32) -> StoreFuture<'_, Vec<Vec<String>>>;
+
+    /// The views and likes per video within the trending windows.
+    fn fetch_trending_counts<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> StoreFuture<'a, HashMap<VideoId, WindowCounts>>;
+}
+
+pub trait UserStore: Send + Sync {
+    /// The profile of the user, an unknown user is an empty user (and not `NotFound`).
+    fn fetch_user<'a>(&'a self, uuid: &'a UserId, config: &'a Config) -> StoreFuture<'a, User>;
+}
+
+/// A store for both, videos and users.
+pub trait Store: VideoStore + UserStore {
+    /// Checks that the store can answer queries at all, for the health of read replicas.
+    fn ping(&self) -> StoreFuture<'_, ()>;
+}
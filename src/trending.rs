@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, database::VideoId};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TrendingVideo {
+    pub uuid: VideoId,
+
+    /// Views and (weighted) likes per hour in the short window
+    pub velocity: f64,
+
+    /// How much faster the video got views and likes in the short window
+    /// than on average in the long window. The list is ranked by this.
+    pub acceleration: f64,
+}
+
+/// The views and likes of a video within the short and the long window.
+#[derive(Default)]
+pub struct WindowCounts {
+    pub short_views: i64,
+    pub long_views: i64,
+    pub short_likes: i64,
+    pub long_likes: i64,
+}
+
+/// Videos that are taking off right now, ranked by how much their view and
+/// like velocity in the short window exceeds their velocity in the long window.
+/// A video that steadily gets views is not trending, one that suddenly gets
+/// a lot of them is.
+#[derive(Default)]
+pub struct TrendingList {
+    videos: Vec<TrendingVideo>,
+}
+
+impl TrendingList {
+    pub fn build(counts: HashMap<VideoId, WindowCounts>, config: &Config) -> Self {
+        let c = &config.trending;
+        let short_hours = c.short_window_seconds as f64 / 3600.;
+        let long_hours = c.long_window_seconds as f64 / 3600.;
+
+        let mut videos: Vec<TrendingVideo> = counts
+            .into_iter()
+            .filter(|(_, counts)| {
+                counts.short_views + counts.short_likes >= c.min_short_window_events as i64
+            })
+            .map(|(uuid, counts)| {
+                let velocity = (counts.short_views as f64
+                    + counts.short_likes as f64 * c.like_weight)
+                    / short_hours;
+                let long_velocity = (counts.long_views as f64
+                    + counts.long_likes as f64 * c.like_weight)
+                    / long_hours;
+
+                TrendingVideo {
+                    uuid,
+                    velocity,
+                    acceleration: velocity - long_velocity,
+                }
+            })
+            .filter(|video| video.acceleration > 0.)
+            .collect();
+
+        videos.sort_by(|a, b| {
+            b.acceleration
+                .partial_cmp(&a.acceleration)
+                .unwrap()
+                .then(a.uuid.cmp(&b.uuid))
+        });
+        videos.truncate(c.list_size as usize);
+
+        Self { videos }
+    }
+
+    /// The trending videos, the most trending first.
+    pub fn videos(&self) -> &[TrendingVideo] {
+        &self.videos
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    database::{User, UserId},
+    store::{StoreError, UserStore},
+};
+
+struct CachedUser {
+    user: User,
+
+    /// The experiment variant the user was fetched for, the fetch amounts
+    /// (liked videos, hashtags etc.) depend on the config of the variant.
+    variant: String,
+    cached_at: Instant,
+    last_used: u64,
+}
+
+struct CachedUsers {
+    users: HashMap<UserId, CachedUser>,
+
+    /// The users by their last use (a counter, increased by every use), so the
+    /// least recently used user is the first one and evicting it is cheap.
+    recently_used: BTreeMap<u64, UserId>,
+    uses: u64,
+
+    /// Increased by every invalidation, so a fetch that was started before
+    /// an invalidation does not put the outdated user back into the cache.
+    generation: u64,
+}
+
+impl CachedUsers {
+    /// Moves the user to the end of the recently used users.
+    fn touch(&mut self, user_id: &UserId) {
+        self.uses += 1;
+        let uses = self.uses;
+        if let Some(entry) = self.users.get_mut(user_id) {
+            self.recently_used.remove(&entry.last_used);
+            self.recently_used.insert(uses, user_id.clone());
+            entry.last_used = uses;
+        }
+    }
+
+    /// Removes the user, returns if it was cached.
+    fn remove(&mut self, user_id: &UserId) -> bool {
+        match self.users.remove(user_id) {
+            Some(entry) => {
+                self.recently_used.remove(&entry.last_used);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub users: usize,
+}
+
+/// Caches the user profiles, so scrolling through the feed does not query the
+/// likes, follows, hashtags etc. of the user again for every page. Users expire
+/// after the TTL (expired users are refetched on their next use) and the least
+/// recently used user is evicted when the cache is full.
+/// A user has to be invalidated when their likes or follows change.
+pub struct UserCache {
+    cached: Mutex<CachedUsers>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl UserCache {
+    pub fn new() -> Self {
+        Self {
+            cached: Mutex::new(CachedUsers {
+                users: HashMap::new(),
+                recently_used: BTreeMap::new(),
+                uses: 0,
+                generation: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached user or fetches (and caches) it from the database. The
+    /// user is only reused for the same experiment variant (and so the same config).
+    pub async fn get(
+        &self,
+        user_id: &UserId,
+        variant: &str,
+        store: &dyn UserStore,
+        config: &Config,
+    ) -> Result<User, StoreError> {
+        let ttl = Duration::from_secs(config.user_cache.ttl_seconds);
+        let generation = {
+            let mut cached = self.cached.lock().unwrap();
+            let user = cached
+                .users
+                .get(user_id)
+                .filter(|entry| entry.variant == variant && entry.cached_at.elapsed() < ttl)
+                .map(|entry| entry.user.clone());
+            if let Some(user) = user {
+                cached.touch(user_id);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(user);
+            }
+
+            cached.generation
+        };
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let user = store.fetch_user(user_id, config).await?;
+
+        let max_users = config.user_cache.max_users as usize;
+        let mut cached = self.cached.lock().unwrap();
+        if cached.generation != generation || max_users == 0 {
+            return Ok(user);
+        }
+
+        cached.remove(user_id);
+        while cached.users.len() >= max_users {
+            let Some((_, least_recently_used)) = cached.recently_used.pop_first() else {
+                break;
+            };
+            cached.users.remove(&least_recently_used);
+        }
+
+        cached.uses += 1;
+        let last_used = cached.uses;
+        cached.recently_used.insert(last_used, user_id.clone());
+        cached.users.insert(
+            user_id.clone(),
+            CachedUser {
+                user: user.clone(),
+                variant: variant.to_string(),
+                cached_at: Instant::now(),
+                last_used,
+            },
+        );
+
+        Ok(user)
+    }
+
+    /// Removes the user from the cache, returns if it was cached.
+    pub fn invalidate(&self, user_id: &UserId) -> bool {
+        let mut cached = self.cached.lock().unwrap();
+        cached.generation += 1;
+        cached.remove(user_id)
+    }
+
+    /// Removes all users, e.g. when the config (and so the amount
+    /// of fetched likes, hashtags etc.) changed.
+    pub fn clear(&self) {
+        let mut cached = self.cached.lock().unwrap();
+        cached.generation += 1;
+        debug!("Clearing {} cached users", cached.users.len());
+        cached.users.clear();
+        cached.recently_used.clear();
+    }
+
+    pub fn stats(&self) -> UserCacheStats {
+        UserCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            users: self.cached.lock().unwrap().users.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::StoreFuture;
+
+    /// Answers every user with an empty profile.
+    struct EmptyUsers;
+
+    impl UserStore for EmptyUsers {
+        fn fetch_user<'a>(&'a self, _: &'a UserId, _: &'a Config) -> StoreFuture<'a, User> {
+            Box::pin(async { Ok(User::default()) })
+        }
+    }
+
+    fn user_id(i: u32) -> UserId {
+        UserId::parse(&format!("00000000-0000-0000-0000-{i:012}")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used_user() {
+        let mut config: Config = serde_json::from_str(include_str!("../config.json")).unwrap();
+        config.user_cache.max_users = 2;
+        let cache = UserCache::new();
+        for i in [1, 2, 1, 3, 1, 2] {
+            cache.get(&user_id(i), "control", &EmptyUsers, &config).await.unwrap();
+        }
+
+        // 2 was evicted by 3 (1 was used more recently), then 3 by 2
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.users), (2, 4, 2));
+    }
+
+    #[tokio::test]
+    async fn users_are_cached_per_variant() {
+        let config: Config = serde_json::from_str(include_str!("../config.json")).unwrap();
+        let cache = UserCache::new();
+        cache.get(&user_id(1), "control", &EmptyUsers, &config).await.unwrap();
+        cache.get(&user_id(1), "treatment", &EmptyUsers, &config).await.unwrap();
+        cache.get(&user_id(1), "treatment", &EmptyUsers, &config).await.unwrap();
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.users), (1, 2, 1));
+    }
+}